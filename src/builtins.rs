@@ -0,0 +1,320 @@
+//! Functions available in every program without being defined, such as
+//! `longueur`. A user-defined function with the same name takes precedence.
+
+use crate::interpreter::Value;
+use crate::lexer;
+use crate::parser::ExecuteError;
+
+/// A builtin receives its already evaluated arguments.
+pub type Builtin = fn(Vec<Value>) -> Result<Value, ExecuteError>;
+
+/// Every builtin, by the name scripts call it with.
+const BUILTINS: &[(&str, Builtin)] = &[
+    ("longueur", longueur),
+    ("typede", typede),
+    ("nombre", nombre),
+    ("texte", texte),
+    ("somme", somme),
+    ("produit", produit),
+    ("min", min),
+    ("max", max),
+    ("abs", abs),
+    ("signe", signe),
+    ("racine", racine),
+    ("plancher", plancher),
+    ("plafond", plafond),
+    ("arrondi", arrondi),
+    ("tronque", tronque),
+    ("sin", |args| Ok(Value::Number(number("sin", args)?.sin()))),
+    ("cos", |args| Ok(Value::Number(number("cos", args)?.cos()))),
+    ("tan", |args| Ok(Value::Number(number("tan", args)?.tan()))),
+    ("sind", sind),
+    ("cosd", cosd),
+    ("tand", tand),
+    ("asin", |args| Ok(Value::Number(unit_interval("asin", args)?.asin()))),
+    ("acos", |args| Ok(Value::Number(unit_interval("acos", args)?.acos()))),
+    ("atan", |args| Ok(Value::Number(number("atan", args)?.atan()))),
+    ("atan2", atan2),
+    ("log", log),
+    ("log10", |args| Ok(Value::Number(positive("log10", args)?.log10()))),
+    ("log2", |args| Ok(Value::Number(positive("log2", args)?.log2()))),
+    ("exp", |args| Ok(Value::Number(number("exp", args)?.exp()))),
+];
+
+pub fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS.iter().find(|(builtin, _)| *builtin == name).map(|(_, builtin)| *builtin)
+}
+
+/// Checks that a builtin taking a fixed number of arguments got `count`.
+fn expect_args(name: &str, args: &[Value], count: usize) -> Result<(), ExecuteError> {
+    if args.len() == count {
+        Ok(())
+    } else {
+        Err(ExecuteError(format!("function '{}' expects {} argument(s), got {}", name, count, args.len())))
+    }
+}
+
+/// Number of elements of an array, or of characters of a string.
+fn longueur(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_args("longueur", &args, 1)?;
+    match &args[0] {
+        Value::Array(elements) => Ok(Value::Number(elements.len() as f64)),
+        Value::Str(text) => Ok(Value::Number(text.chars().count() as f64)),
+        value => Err(ExecuteError(format!("'longueur' expects a tableau or a texte, got {}", value.type_name()))),
+    }
+}
+
+/// Name of the type of a value: `nombre`, `texte` or `tableau`. Booleans
+/// are numbers, so `typede(vrai)` is `nombre`.
+fn typede(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_args("typede", &args, 1)?;
+    Ok(Value::Str(args[0].type_name().to_string()))
+}
+
+/// A number read from a string like a number literal (`3,5`, `1_000`,
+/// `0x1F`...). A number is returned as it is.
+fn nombre(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_args("nombre", &args, 1)?;
+    match &args[0] {
+        Value::Number(n) => Ok(Value::Number(*n)),
+        Value::Str(text) => lexer::number(text)
+            .map(Value::Number)
+            .ok_or_else(|| ExecuteError(format!("'nombre' cannot read a number from '{}'", text))),
+        value => Err(ExecuteError(format!("'nombre' expects a nombre or a texte, got {}", value.type_name()))),
+    }
+}
+
+/// A value as text, written the way `zipette` prints it.
+fn texte(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_args("texte", &args, 1)?;
+    Ok(Value::Str(args[0].to_string()))
+}
+
+/// Sum of any number of numbers, 0 for none.
+fn somme(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    // Not `sum()`, which gives -0 for no numbers.
+    Ok(Value::Number(numbers("somme", args)?.fold(0.0, |sum, n| sum + n)))
+}
+
+/// Product of any number of numbers, 1 for none.
+fn produit(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    Ok(Value::Number(numbers("produit", args)?.product()))
+}
+
+/// Absolute value of a number.
+fn abs(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    Ok(Value::Number(number("abs", args)?.abs()))
+}
+
+/// -1, 0 or 1 depending on the sign of a number, NaN for NaN.
+fn signe(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    let n = number("signe", args)?;
+    // Not `signum()`, which gives 1 for 0 and -1 for -0.
+    let sign = if n > 0.0 { 1.0 } else if n < 0.0 { -1.0 } else if n == 0.0 { 0.0 } else { f64::NAN };
+    Ok(Value::Number(sign))
+}
+
+/// Square root of a number, or its `n`th root with a second argument.
+/// Negative numbers only have odd roots.
+fn racine(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_one_or_two_args("racine", &args)?;
+    let args: Vec<f64> = numbers("racine", args)?.collect();
+    let (x, n) = (args[0], args.get(1).copied().unwrap_or(2.0));
+    if n == 0.0 {
+        return Err(ExecuteError("'racine' cannot take root 0 of a number".to_string()));
+    }
+    let odd = n.fract() == 0.0 && n % 2.0 != 0.0;
+    if x < 0.0 && !odd {
+        return Err(ExecuteError(format!("'racine' cannot take root {} of the negative number {}", n, x)));
+    }
+    let root = if n == 2.0 { x.sqrt() } else { x.signum() * x.abs().powf(1.0 / n) };
+    // `powf` misses exact roots by a rounding error, `racine(27, 3)` giving
+    // 3.0000000000000004: keep the integer when it is the exact root.
+    let rounded = root.round();
+    Ok(Value::Number(if rounded.powf(n) == x { rounded } else { root }))
+}
+
+/// Largest integer less than or equal to a number.
+fn plancher(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    Ok(Value::Number(number("plancher", args)?.floor()))
+}
+
+/// Smallest integer greater than or equal to a number.
+fn plafond(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    Ok(Value::Number(number("plafond", args)?.ceil()))
+}
+
+/// Integer part of a number, dropping the decimals.
+fn tronque(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    Ok(Value::Number(number("tronque", args)?.trunc()))
+}
+
+/// Rounds a number half away from zero, to the nearest integer or to `n`
+/// decimal places, a negative `n` rounding to tens, hundreds and so on.
+///
+/// The number is multiplied by 10^n, rounded, then divided back, so the
+/// result is that of the scaled product rather than of the decimal written
+/// in the script: `arrondi(2.675, 2)` gives 2.68, but `arrondi(1.005, 2)`
+/// gives 1 as 1.005 * 100 is 100.49999999999999.
+fn arrondi(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_one_or_two_args("arrondi", &args)?;
+    let args: Vec<f64> = numbers("arrondi", args)?.collect();
+    let (x, places) = (args[0], args.get(1).copied().unwrap_or(0.0));
+    if places.fract() != 0.0 {
+        return Err(ExecuteError(format!("'arrondi' needs a whole number of decimal places, got {}", places)));
+    }
+    // Dividing by 10^-n rather than multiplying by 10^n, which cannot hold
+    // 0.1 exactly.
+    let rounded = if places >= 0.0 {
+        let scale = 10f64.powf(places);
+        (x * scale).round() / scale
+    } else {
+        let scale = 10f64.powf(-places);
+        (x / scale).round() * scale
+    };
+    Ok(Value::Number(rounded))
+}
+
+/// Sine of an angle in degrees, exact at multiples of 90.
+fn sind(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    let degrees = number("sind", args)?;
+    let sine = match quarter_turns(degrees) {
+        Some(0 | 2) => 0.0,
+        Some(1) => 1.0,
+        Some(_) => -1.0,
+        None => degrees.to_radians().sin(),
+    };
+    Ok(Value::Number(sine))
+}
+
+/// Cosine of an angle in degrees, exact at multiples of 90.
+fn cosd(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    let degrees = number("cosd", args)?;
+    let cosine = match quarter_turns(degrees) {
+        Some(1 | 3) => 0.0,
+        Some(0) => 1.0,
+        Some(_) => -1.0,
+        None => degrees.to_radians().cos(),
+    };
+    Ok(Value::Number(cosine))
+}
+
+/// Tangent of an angle in degrees, which has none at 90 plus a multiple
+/// of 180.
+fn tand(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    let degrees = number("tand", args)?;
+    match quarter_turns(degrees) {
+        Some(0 | 2) => Ok(Value::Number(0.0)),
+        Some(_) => Err(ExecuteError(format!("'tand' is not defined for {}", degrees))),
+        None => Ok(Value::Number(degrees.to_radians().tan())),
+    }
+}
+
+/// Where an angle in degrees that is a multiple of 90 lands, from 0 for
+/// the right to 3 for the bottom. Converting it to radians would lose
+/// the exact values, `sin(PI)` not being 0.
+fn quarter_turns(degrees: f64) -> Option<u8> {
+    (degrees % 90.0 == 0.0).then(|| (degrees / 90.0).rem_euclid(4.0) as u8)
+}
+
+/// Angle in radians whose tangent is `y / x`, in the quadrant of the
+/// point (x, y).
+fn atan2(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_args("atan2", &args, 2)?;
+    let args: Vec<f64> = numbers("atan2", args)?.collect();
+    Ok(Value::Number(args[0].atan2(args[1])))
+}
+
+/// The argument of `asin` or `acos`, which only exist from -1 to 1.
+fn unit_interval(name: &str, args: Vec<Value>) -> Result<f64, ExecuteError> {
+    let n = number(name, args)?;
+    if (-1.0..=1.0).contains(&n) {
+        Ok(n)
+    } else {
+        Err(ExecuteError(format!("'{}' needs a number between -1 and 1, got {}", name, n)))
+    }
+}
+
+/// Natural logarithm of a number, or its logarithm in the base given as a
+/// second argument.
+fn log(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    expect_one_or_two_args("log", &args)?;
+    let args: Vec<f64> = numbers("log", args)?.collect();
+    let x = check_positive("log", args[0])?;
+    let Some(&base) = args.get(1) else {
+        return Ok(Value::Number(x.ln()));
+    };
+    if base <= 0.0 || base == 1.0 {
+        return Err(ExecuteError(format!("'log' needs a positive base other than 1, got {}", base)));
+    }
+    let log = match base {
+        2.0 => x.log2(),
+        10.0 => x.log10(),
+        _ => x.ln() / base.ln(),
+    };
+    // Dividing logarithms misses exact powers by a rounding error, as in
+    // `log(243, 3)` being 4.999999999999999: keep the integer when it is
+    // exact.
+    let rounded = log.round();
+    Ok(Value::Number(if base.powf(rounded) == x { rounded } else { log }))
+}
+
+/// The argument of a logarithm, which only exists for positive numbers.
+fn positive(name: &str, args: Vec<Value>) -> Result<f64, ExecuteError> {
+    check_positive(name, number(name, args)?)
+}
+
+fn check_positive(name: &str, n: f64) -> Result<f64, ExecuteError> {
+    if n > 0.0 {
+        Ok(n)
+    } else {
+        Err(ExecuteError(format!("'{}' needs a positive number, got {}", name, n)))
+    }
+}
+
+/// Smallest of two or more numbers, NaN if any of them is.
+fn min(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    extreme("min", args, f64::min)
+}
+
+/// Largest of two or more numbers, NaN if any of them is.
+fn max(args: Vec<Value>) -> Result<Value, ExecuteError> {
+    extreme("max", args, f64::max)
+}
+
+/// Reduces two or more numbers with `pick`, which would skip NaN.
+fn extreme(name: &str, args: Vec<Value>, pick: fn(f64, f64) -> f64) -> Result<Value, ExecuteError> {
+    if args.len() < 2 {
+        return Err(ExecuteError(format!("function '{}' expects at least 2 argument(s), got {}", name, args.len())));
+    }
+    let result = numbers(name, args)?
+        .reduce(|a, b| if a.is_nan() || b.is_nan() { f64::NAN } else { pick(a, b) })
+        .expect("there are at least 2 numbers");
+    Ok(Value::Number(result))
+}
+
+/// Checks that a builtin with an optional second argument got 1 or 2.
+fn expect_one_or_two_args(name: &str, args: &[Value]) -> Result<(), ExecuteError> {
+    if (1..=2).contains(&args.len()) {
+        Ok(())
+    } else {
+        Err(ExecuteError(format!("wrong number of arguments to '{}': expected 1 or 2, got {}", name, args.len())))
+    }
+}
+
+/// The argument of a builtin taking a single number.
+fn number(name: &str, args: Vec<Value>) -> Result<f64, ExecuteError> {
+    expect_args(name, &args, 1)?;
+    numbers(name, args).map(|mut numbers| numbers.next().expect("there is 1 argument"))
+}
+
+/// The arguments of a builtin that only takes numbers.
+fn numbers(name: &str, args: Vec<Value>) -> Result<impl Iterator<Item = f64>, ExecuteError> {
+    args.into_iter()
+        .map(|arg| match arg {
+            Value::Number(n) => Ok(n),
+            value => Err(ExecuteError(format!("'{}' expects nombre arguments, got {}", name, value.type_name()))),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Vec::into_iter)
+}
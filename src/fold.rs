@@ -0,0 +1,159 @@
+//! Constant folding: operations on number literals are computed once
+//! before the program runs, with the same rules as at runtime.
+
+use std::rc::Rc;
+use crate::interpreter::Value;
+use crate::parser::{Expression, Statement, StringPart};
+
+/// Replaces every unary or binary operation whose operands are number
+/// literals with its result, innermost first, so `2 ** 10 + 1` becomes
+/// `1025`. Anything involving a name is left for runtime, so `x + 1 + 2`,
+/// which is `(x + 1) + 2`, stays as it is.
+pub fn fold_constants(program: &mut [Statement]) {
+    program.iter_mut().for_each(fold_statement);
+}
+
+fn fold_statement(statement: &mut Statement) {
+    match statement {
+        Statement::Expression(expr)
+        | Statement::Assignment(_, expr)
+        | Statement::CompoundAssignment(_, _, expr)
+        | Statement::Constant(_, expr)
+        | Statement::TypedAssignment(_, _, expr)
+        | Statement::Assert(expr)
+        | Statement::Sleep(expr)
+        | Statement::Return(expr) => fold(expr),
+        Statement::Exit(code) => code.iter_mut().for_each(fold),
+        Statement::Input { prompt, .. } => prompt.iter_mut().for_each(fold),
+        Statement::Print(values) | Statement::Write(values) | Statement::PrintColored(_, _, values) => {
+            values.iter_mut().for_each(fold)
+        }
+        Statement::PrintColoredDyn(color, values) => {
+            fold(color);
+            values.iter_mut().for_each(fold);
+        }
+        Statement::PrintColoredRgb(components, values) => {
+            components.iter_mut().for_each(fold);
+            values.iter_mut().for_each(fold);
+        }
+        Statement::Block(statements) => fold_constants(statements),
+        Statement::If { cond, then_block, else_block } => {
+            fold(cond);
+            fold_constants(then_block);
+            else_block.iter_mut().for_each(|block| fold_constants(block));
+        }
+        Statement::While { cond, body } | Statement::DoWhile { body, cond } | Statement::Repeat { count: cond, body } => {
+            fold(cond);
+            fold_constants(body);
+        }
+        Statement::Match { subject, arms, default } => {
+            fold(subject);
+            for (pattern, block) in arms {
+                fold(pattern);
+                fold_constants(block);
+            }
+            default.iter_mut().for_each(|block| fold_constants(block));
+        }
+        Statement::FunctionDef(function) => {
+            let function = Rc::make_mut(function);
+            function.params.iter_mut().filter_map(|param| param.default.as_mut()).for_each(fold);
+            fold_constants(&mut function.body);
+        }
+        Statement::Commented { statement, .. } => fold_statement(statement),
+        Statement::Step { .. } | Statement::Forget(_) | Statement::Import(_) | Statement::Break | Statement::Continue | Statement::Comments(_) => {}
+    }
+}
+
+fn fold(expr: &mut Expression) {
+    let folded = match expr {
+        Expression::Binary { op, left, right } => {
+            fold(left);
+            fold(right);
+            match (&**left, &**right) {
+                (Expression::Number(left), Expression::Number(right)) => op.apply(Value::Number(*left), Value::Number(*right)).ok(),
+                _ => None,
+            }
+        }
+        Expression::Unary { op, expr } => {
+            fold(expr);
+            match &**expr {
+                Expression::Number(n) => op.apply(Value::Number(*n)).ok(),
+                _ => None,
+            }
+        }
+        Expression::Array(elements) => {
+            elements.iter_mut().for_each(fold);
+            None
+        }
+        Expression::Call { args, .. } => {
+            args.iter_mut().for_each(fold);
+            None
+        }
+        Expression::Interpolation(parts) => {
+            for part in parts {
+                if let StringPart::Expression(expr) = part {
+                    fold(expr);
+                }
+            }
+            None
+        }
+        Expression::Random(range) => {
+            range.iter_mut().for_each(|range| range.iter_mut().for_each(fold));
+            None
+        }
+        Expression::Index { target, index } => {
+            fold(target);
+            fold(index);
+            None
+        }
+        Expression::Conditional { cond, then, otherwise } => {
+            fold(cond);
+            fold(then);
+            fold(otherwise);
+            None
+        }
+        Expression::Logical { left, right, .. } => {
+            fold(left);
+            fold(right);
+            None
+        }
+        Expression::Number(_) | Expression::Str(_) | Expression::Identifier(_) | Expression::Clock => None,
+    };
+    if let Some(Value::Number(n)) = folded {
+        *expr = Expression::Number(n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::{BinaryExpressionType, Parser};
+
+    fn folded(source: &str) -> Vec<Statement> {
+        let mut program = Parser::new(Lexer::new(source)).parse().unwrap();
+        fold_constants(&mut program);
+        program
+    }
+
+    fn sum(left: Expression, right: Expression) -> Expression {
+        Expression::Binary { op: BinaryExpressionType::Sum, left: Box::new(left), right: Box::new(right) }
+    }
+
+    #[test]
+    fn literal_operations_fold() {
+        assert_eq!(folded("zipette 2 ** 10 + 1;"), [Statement::Print(vec![Expression::Number(1025.0)])]);
+        assert_eq!(folded("vicer x 2 ** -1 * ~0 < 3;"), folded("vicer x 0;"));
+        assert_eq!(folded("lsd red (1 + 2) * 3;"), folded("lsd red 9;"));
+        assert_eq!(folded("fonction f() { si 1 == 1 { renvoie [1 + 1, g(2 * 2)]; }; };"),
+            folded("fonction f() { si 1 { renvoie [2, g(4)]; }; };"));
+    }
+
+    #[test]
+    fn names_are_left_alone() {
+        let x = || Expression::Identifier("x".to_string());
+        assert_eq!(folded("zipette x + 1 + 2;"), [Statement::Print(vec![sum(sum(x(), Expression::Number(1.0)), Expression::Number(2.0))])]);
+        assert_eq!(folded("zipette x + (1 + 2);"), [Statement::Print(vec![sum(x(), Expression::Number(3.0))])]);
+        assert_eq!(folded("zipette \"a\" + 1;"), Parser::new(Lexer::new("zipette \"a\" + 1;")).parse().unwrap());
+    }
+}
@@ -1,15 +1,333 @@
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
 use colored::Colorize;
-use crate::parser::{Statement};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use crate::lexer;
+use crate::parser::{self, ControlFlow, ExecuteError, Function, Statement};
+
+/// Function calls that may be nested before the program is stopped, well
+/// before recursion could overflow the interpreter's own stack.
+pub const MAX_CALL_DEPTH: usize = 100;
+
+/// Constants every program starts with.
+const PREDEFINED: [(&str, f64); 2] = [("PI", std::f64::consts::PI), ("E", std::f64::consts::E)];
+
+/// A runtime value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    Str(String),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    /// The type's name in the language, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "nombre",
+            Value::Str(_) => "texte",
+            Value::Array(_) => "tableau",
+        }
+    }
+
+    /// Whether the value counts as true in a condition: a non-zero number, or
+    /// a non-empty string or array.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Number(n) => *n != 0.0,
+            Value::Str(text) => !text.is_empty(),
+            Value::Array(elements) => !elements.is_empty(),
+        }
+    }
+}
+
+/// Strings print their content, without quotes, except inside an array
+/// (`[1, "a"]`).
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(text) => write!(f, "{}", text),
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    match element {
+                        Value::Str(text) => write!(f, "{:?}", text)?,
+                        element => write!(f, "{}", element)?,
+                    }
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// A variable in a scope.
+struct Binding {
+    value: Value,
+    /// Declared with `fixe`, so it cannot be assigned again.
+    constant: bool,
+}
+
+/// State that statements read and update while a program runs.
+pub struct Environment {
+    /// Variable scopes, the innermost last. The global scope is never popped.
+    scopes: Vec<HashMap<String, Binding>>,
+    pub output: Box<dyn Write>,
+    /// Where `demande` reads its lines from. Stdin is only locked while a
+    /// line is read, so that other environments can use it too.
+    pub input: Box<dyn BufRead>,
+    /// Called by `attend`, `thread::sleep` unless replaced for tests.
+    pub sleep: Box<dyn FnMut(Duration)>,
+    /// Read by `horloge`: milliseconds since the environment was created,
+    /// unless replaced for tests.
+    pub clock: Box<dyn FnMut() -> f64>,
+    /// Iterations a single loop may run before it is stopped as runaway.
+    pub max_iterations: Option<usize>,
+    /// Source of `hasard` and of the `multicolor` colors.
+    pub rng: StdRng,
+    functions: HashMap<String, Rc<Function>>,
+    call_depth: usize,
+    /// Exit code of a `quitte` run inside a function, see `request_exit`.
+    exit_code: Option<i32>,
+    /// Directory that `importe` paths are relative to.
+    base_dir: PathBuf,
+    /// Canonical paths of the files being run, the outermost first.
+    imports: Vec<PathBuf>,
+    /// Columns a tab spans in the positions of errors in imported files, see
+    /// `Lexer::with_tab_width`.
+    pub tab_width: usize,
+}
+
+impl Environment {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        let globals = PREDEFINED.iter()
+            .map(|(name, value)| (name.to_string(), Binding { value: Value::Number(*value), constant: true }))
+            .collect();
+        Self { scopes: vec![globals], output, input: Box::new(BufReader::new(io::stdin())), sleep: Box::new(thread::sleep), clock: Box::new(since(Instant::now())), max_iterations: None, rng: StdRng::from_os_rng(), functions: HashMap::new(), call_depth: 0, exit_code: None, base_dir: PathBuf::new(), imports: Vec::new(), tab_width: 1 }
+    }
+
+    /// Looks `name` up from the innermost scope outward.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).map(|binding| binding.value.clone())
+    }
+
+    /// Updates the closest existing `name`, or creates it in the innermost
+    /// scope. Constants cannot be updated.
+    pub fn assign(&mut self, name: &str, value: Value) -> Result<(), ExecuteError> {
+        match self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            Some(binding) if binding.constant => return Err(ExecuteError(format!("cannot reassign constant '{}'", name))),
+            Some(binding) => binding.value = value,
+            None => {
+                self.innermost().insert(name.to_string(), Binding { value, constant: false });
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares `name` in the innermost scope, hiding any outer `name`.
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.innermost().insert(name.to_string(), Binding { value, constant: false });
+    }
+
+    /// Declares the constant `name` in the innermost scope, unless the closest
+    /// `name` is already a constant.
+    pub fn define_constant(&mut self, name: &str, value: Value) -> Result<(), ExecuteError> {
+        if let Some(binding) = self.scopes.iter().rev().find_map(|scope| scope.get(name))
+            && binding.constant {
+            return Err(ExecuteError(format!("cannot reassign constant '{}'", name)));
+        }
+        self.innermost().insert(name.to_string(), Binding { value, constant: true });
+        Ok(())
+    }
+
+    /// Removes the closest `name`, which must exist and not be a constant.
+    pub fn forget(&mut self, name: &str) -> Result<(), ExecuteError> {
+        match self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            None => Err(ExecuteError(format!("cannot forget undefined variable {}", name))),
+            Some(binding) if binding.constant => Err(ExecuteError(format!("cannot forget constant '{}'", name))),
+            Some(_) => {
+                self.remove(name);
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the closest `name`, if any.
+    pub fn remove(&mut self, name: &str) {
+        if let Some(scope) = self.scopes.iter_mut().rev().find(|scope| scope.contains_key(name)) {
+            scope.remove(name);
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Makes `function` callable by its name, replacing any previous definition.
+    pub fn define_function(&mut self, function: Rc<Function>) {
+        self.functions.insert(function.name.clone(), function);
+    }
+
+    pub fn function(&self, name: &str) -> Option<Rc<Function>> {
+        self.functions.get(name).cloned()
+    }
+
+    /// Number of function calls currently running.
+    pub fn call_depth(&self) -> usize {
+        self.call_depth
+    }
+
+    /// Records a `quitte` that has to unwind out of an expression, which can
+    /// only stop with an error. `Interpreter::run` then reports the exit.
+    pub fn request_exit(&mut self, code: i32) {
+        self.exit_code = Some(code);
+    }
+
+    /// Runs `body` as a function call: the caller's local scopes are hidden,
+    /// leaving the globals and a fresh scope holding `arguments`.
+    pub fn call<T>(&mut self, arguments: impl IntoIterator<Item = (String, Value)>, body: impl FnOnce(&mut Self) -> T) -> T {
+        let caller = self.scopes.split_off(1);
+        self.scopes.push(arguments.into_iter().map(|(name, value)| (name, Binding { value, constant: false })).collect());
+        self.call_depth += 1;
+        let result = body(self);
+        self.call_depth -= 1;
+        self.scopes.truncate(1);
+        self.scopes.extend(caller);
+        result
+    }
+
+    /// Where `importe` looks for `file`.
+    pub fn import_path(&self, file: &str) -> PathBuf {
+        self.base_dir.join(file)
+    }
+
+    /// Runs `body` as the import of the file at the canonical `path`: the
+    /// imports it makes are relative to its directory, and it cannot import
+    /// a file that is still being run.
+    pub fn import<T>(&mut self, path: PathBuf, body: impl FnOnce(&mut Self) -> Result<T, ExecuteError>) -> Result<T, ExecuteError> {
+        if let Some(start) = self.imports.iter().position(|running| *running == path) {
+            let cycle: Vec<String> = self.imports[start..].iter().chain([&path]).map(|path| path.display().to_string()).collect();
+            return Err(ExecuteError(format!("circular import: {}", cycle.join(" -> "))));
+        }
+        let base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let includer = std::mem::replace(&mut self.base_dir, base_dir);
+        self.imports.push(path);
+        let result = body(self);
+        self.imports.pop();
+        self.base_dir = includer;
+        result
+    }
+
+    fn innermost(&mut self) -> &mut HashMap<String, Binding> {
+        self.scopes.last_mut().expect("the global scope is never popped")
+    }
+}
+
+/// A clock giving the milliseconds elapsed since `start`.
+fn since(start: Instant) -> impl FnMut() -> f64 {
+    move || start.elapsed().as_secs_f64() * 1000.0
+}
+
+/// How a program that did not fail ended.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// It ran to its end, or to a top-level `renvoie` with this value.
+    Returned(Value),
+    /// It ran `quitte` with this exit code.
+    Exited(i32),
+}
 
 pub struct Interpreter {
     program: Vec<Statement>,
-    variables: HashMap<String, f64>,
+    environment: Environment,
 }
 
 impl Interpreter {
     pub fn new(program: Vec<Statement>) -> Self {
-        Self { program, variables: HashMap::new() }
+        Self::with_output(program, Box::new(io::stdout()))
+    }
+
+    pub fn with_output(program: Vec<Statement>, output: Box<dyn Write>) -> Self {
+        Self { program, environment: Environment::new(output) }
+    }
+
+    /// Reads `demande` input from `input` instead of stdin.
+    pub fn with_input(mut self, input: Box<dyn BufRead>) -> Self {
+        self.environment.input = input;
+        self
+    }
+
+    /// Calls `sleep` for `attend` instead of pausing the thread, so that
+    /// tests can check the durations without waiting for them.
+    pub fn with_sleep(mut self, sleep: impl FnMut(Duration) + 'static) -> Self {
+        self.environment.sleep = Box::new(sleep);
+        self
+    }
+
+    /// Treats the program as read from `path`, so that its imports are
+    /// relative to the file's directory and importing it back is a cycle.
+    pub fn with_path(mut self, path: &Path) -> Self {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.environment.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.environment.imports = vec![path];
+        self
+    }
+
+    /// Lexes imported files with `Lexer::with_tab_width(width)`, so that the
+    /// errors in them have the same columns as in the program itself.
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        self.environment.tab_width = width;
+        self
+    }
+
+    /// Draws the random numbers from `seed`, so that runs can be repeated.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.environment.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Calls `clock` for `horloge` instead of reading the time, so that tests
+    /// can decide how much of it passes.
+    pub fn with_clock(mut self, clock: impl FnMut() -> f64 + 'static) -> Self {
+        self.environment.clock = Box::new(clock);
+        self
+    }
+
+    /// Makes the command line arguments given to the script visible to it as
+    /// `arguments`, `arg1`, `arg2`... and `nbargs`. Those that read as a
+    /// number are numbers, the others strings.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        let values: Vec<Value> = args.into_iter()
+            .map(|arg| lexer::number(&arg).map(Value::Number).unwrap_or(Value::Str(arg)))
+            .collect();
+        for (i, value) in values.iter().enumerate() {
+            self.environment.define(&format!("arg{}", i + 1), value.clone());
+        }
+        self.environment.define("nbargs", Value::Number(values.len() as f64));
+        self.environment.define("arguments", Value::Array(values));
+        self
+    }
+
+    /// Turns any loop running more than `max` iterations into an error.
+    pub fn with_max_iterations(mut self, max: usize) -> Self {
+        self.environment.max_iterations = Some(max);
+        self
     }
 
     pub fn show(&self) {
@@ -18,16 +336,838 @@ impl Interpreter {
         }
     }
 
-    pub fn interpret(mut self) -> f64 {
-        let _ = self.
-            program.
-            into_iter().
-            try_for_each(|c|{
-                c.execute(&mut self.variables)
-            })
-            .inspect_err(|err|{
+    /// Runs the program until its end, a top-level `renvoie` or a `quitte`.
+    /// A program without `renvoie` returns 0.
+    pub fn run(mut self) -> Result<Outcome, ExecuteError> {
+        match parser::execute_all(&self.program, &mut self.environment) {
+            Ok(ControlFlow::Return(value)) => Ok(Outcome::Returned(value)),
+            Ok(ControlFlow::Exit(code)) => Ok(Outcome::Exited(code)),
+            Ok(ControlFlow::Normal | ControlFlow::Break | ControlFlow::Continue) => Ok(Outcome::Returned(Value::Number(0.0))),
+            Err(err) => match self.environment.exit_code {
+                Some(code) => Ok(Outcome::Exited(code)),
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Runs the program and returns the process exit code: the one given to
+    /// `quitte`, 0 otherwise, or 1 after printing the error if it fails.
+    pub fn interpret(self) -> i32 {
+        match self.run() {
+            Ok(Outcome::Exited(code)) => code,
+            Ok(Outcome::Returned(_)) => 0,
+            Err(err) => {
                 println!("{}", err.to_string().red());
-            });
-        1.0
+                1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+    use crate::fold::fold_constants;
+    use crate::lexer::Lexer;
+    use crate::parser::{Expression, Parser};
+
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A temporary directory, deleted with everything in it when dropped.
+    struct TempDir(PathBuf);
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A fresh directory holding `files`, given as name and content.
+    fn write_files(test: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("zipette-{}-{}", test, std::process::id()));
+        for (name, content) in files {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        TempDir(dir.canonicalize().unwrap())
+    }
+
+    fn parse(source: &str) -> Vec<Statement> {
+        Parser::new(Lexer::new(source)).parse().expect("program should parse")
+    }
+
+    /// Hands an interpreter for `program` to `run`, returning what `run`
+    /// returned and what the program printed.
+    fn output_of<T>(program: Vec<Statement>, run: impl FnOnce(Interpreter) -> T) -> (T, String) {
+        let output = SharedOutput::default();
+        let result = run(Interpreter::with_output(program, Box::new(output.clone())));
+        let bytes = output.0.borrow().clone();
+        (result, String::from_utf8(bytes).unwrap())
+    }
+
+    /// Runs `source` on an interpreter set up by `configure`, returning what
+    /// it printed.
+    fn run_with(source: &str, configure: impl FnOnce(Interpreter) -> Interpreter) -> Result<String, ExecuteError> {
+        let (result, output) = output_of(parse(source), |interpreter| configure(interpreter).run());
+        result.map(|_| output)
+    }
+
+    fn run_file(path: &Path) -> Result<String, ExecuteError> {
+        run_with(&std::fs::read_to_string(path).unwrap(), |interpreter| interpreter.with_path(path))
+    }
+
+    fn run(source: &str) -> Result<String, ExecuteError> {
+        run_with(source, |interpreter| interpreter)
+    }
+
+    fn run_program(program: Vec<Statement>) -> Result<String, ExecuteError> {
+        let (result, output) = output_of(program, Interpreter::run);
+        result.map(|_| output)
+    }
+
+    #[test]
+    fn identifiers_with_digits_and_underscores() {
+        assert_eq!(run("vicer foo_2 10; zipette foo_2;").unwrap(), "10\n");
+    }
+
+    #[test]
+    fn modulo_binds_like_product() {
+        assert_eq!(run("zipette 10 % 3;").unwrap(), "1\n");
+        assert_eq!(run("zipette 1 + 10 % 4 * 2;").unwrap(), "5\n");
+        assert_eq!(run("zipette -7 % 3;").unwrap(), "-1\n");
+    }
+
+    #[test]
+    fn modulo_by_zero_is_nan() {
+        assert_eq!(run("zipette 5 % 0;").unwrap(), "NaN\n");
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(run("zipette 12 & 10;").unwrap(), "8\n");
+        assert_eq!(run("zipette 12 | 3;").unwrap(), "15\n");
+        assert_eq!(run("zipette 12 xor 10;").unwrap(), "6\n");
+        assert_eq!(run("zipette ~0 & 255;").unwrap(), "255\n");
+        assert_eq!(run("zipette 12.9 & 10.2;").unwrap(), "8\n");
+    }
+
+    #[test]
+    fn bitwise_precedence() {
+        assert_eq!(run("zipette 1 | 2 & 3;").unwrap(), "3\n");
+        assert_eq!(run("zipette 2 + 1 & 3;").unwrap(), "3\n");
+        assert_eq!(run("zipette 6 xor 3 | 8;").unwrap(), "13\n");
+        assert_eq!(run("zipette 1 << 2 & 4;").unwrap(), "4\n");
+    }
+
+    #[test]
+    fn compound_assignments_accumulate() {
+        let source = "vicer total 1; vicer total += 4; total *= 3; total -= 5; total /= 2; zipette total;";
+        assert_eq!(run(source).unwrap(), "5\n");
+    }
+
+    #[test]
+    fn compound_assignment_on_undefined_variable_fails() {
+        let err = run("total += 1;").unwrap_err();
+        assert_eq!(err.to_string(), "[EXECUTION] Error : use of undefined variable total");
+    }
+
+    #[test]
+    fn blocks_run_their_statements_in_order() {
+        assert_eq!(run("vicer x 1; { { zipette x; x += 1; }; zipette x; }; zipette x;").unwrap(), "1\n2\n2\n");
+    }
+
+    #[test]
+    fn typed_assignment_stores_the_value() {
+        assert_eq!(run("vicer x: nombre 5; zipette x * 2;").unwrap(), "10\n");
+    }
+
+    #[test]
+    fn conditional_expression() {
+        assert_eq!(run("vicer a 5; zipette a ? 1 : 2; zipette a - 5 ? 1 : 2;").unwrap(), "1\n2\n");
+        assert_eq!(run("zipette 0 ? 1 : 0 ? 2 : 3;").unwrap(), "3\n");
+    }
+
+    #[test]
+    fn conditional_only_evaluates_the_taken_branch() {
+        assert_eq!(run("vicer x 0; zipette x ? 1 / x : 0; zipette 1 ? 7 : missing;").unwrap(), "0\n7\n");
+    }
+
+    #[test]
+    fn increment_and_decrement() {
+        assert_eq!(run("vicer i 0; i++; i++; zipette i;").unwrap(), "2\n");
+        assert_eq!(run("vicer x 3; --x; x--; zipette x;").unwrap(), "1\n");
+        assert_eq!(run("n++;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable n");
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(run("zipette 3 -2; zipette 3 - 2; zipette -2;").unwrap(), "1\n1\n-2\n");
+        assert_eq!(run("zipette (-2) ** 2; zipette -2 ** 2; zipette 2 ** -1; zipette -2 ** -2;").unwrap(), "4\n-4\n0.5\n-0.25\n");
+        assert_eq!(run("vicer x 5; zipette x -5; zipette 2 * -x;").unwrap(), "0\n-10\n");
+    }
+
+    #[test]
+    fn windows_line_endings_and_bom() {
+        let lf = "vicer x 2;\nzipette x * 3;\n";
+        let windows = "\u{feff}vicer x 2;\r\nzipette x * 3;\r\n";
+        assert_eq!(run(windows).unwrap(), run(lf).unwrap());
+    }
+
+    #[test]
+    fn string_interpolation() {
+        assert_eq!(
+            run("vicer total 12; vicer count 3; zipette \"total = ${total}, mean = ${total / count}.\"; zipette \"done\";").unwrap(),
+            "total = 12, mean = 4.\ndone\n"
+        );
+        assert_eq!(run("zipette \"${missing}\";").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable missing");
+    }
+
+    #[test]
+    fn lenient_keywords_keep_variables_case_sensitive() {
+        let program = Parser::new(Lexer::new("Vicer x 1; vicer X 2; Zipette x; ZIPETTE X;")).ignore_keyword_case().parse().unwrap();
+        assert_eq!(run_program(program).unwrap(), "1\n2\n");
+    }
+
+    #[test]
+    fn shebang_script() {
+        assert_eq!(run("#!/usr/bin/env zipette-interpreter\nvicer x 4;\nzipette x * x;\n").unwrap(), "16\n");
+    }
+
+    #[test]
+    fn nested_negations() {
+        assert_eq!(run("vicer x 3; zipette -(2 + 3); zipette --x; zipette - -x; zipette -x ** 2; zipette 1 --x;").unwrap(), "-5\n3\n3\n-9\n4\n");
+    }
+
+    #[test]
+    fn unary_plus() {
+        assert_eq!(run("zipette +5; vicer x +(2*3); zipette x; zipette 1 ++x; zipette -+x;").unwrap(), "5\n6\n7\n-6\n");
+        assert_eq!(run("zipette +undefined_var;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable undefined_var");
+    }
+
+    #[test]
+    fn shift_precedence() {
+        assert_eq!(run("zipette 1 << 2 + 3; zipette 16 >> 1 + 1; zipette 2 + 1 << 3; zipette 2 * 3 << 1;").unwrap(), "32\n4\n24\n12\n");
+    }
+
+    #[test]
+    fn comparisons_yield_one_or_zero() {
+        assert_eq!(run("zipette 3 >= 2; zipette 2 == 3;").unwrap(), "1\n0\n");
+        assert_eq!(run("vicer x 4; zipette x != 4; zipette x < 5; zipette x > 5; zipette x <= 4;").unwrap(), "0\n1\n0\n1\n");
+        assert_eq!(run("vicer a 3; vicer b 7; vicer max a > b ? a : b; zipette max;").unwrap(), "7\n");
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(run("vicer x 0; zipette x != 0 && 10 / x > 1; zipette x != 0 && missing; zipette x == 0 || missing;").unwrap(), "0\n0\n1\n");
+        assert_eq!(run("zipette 1 && missing;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable missing");
+        assert_eq!(run("zipette 0 || missing;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable missing");
+        assert_eq!(run("zipette 2 && 3; zipette 0 || 0; zipette (1 || 0) && (0 || 5); zipette 1 || 0 && 0;").unwrap(), "1\n0\n1\n1\n");
+    }
+
+    #[test]
+    fn boolean_literals_print_as_numbers() {
+        assert_eq!(run("zipette vrai; zipette faux; vicer ok vrai; zipette ok && 3 > 2; zipette faux ? 1 : 2;").unwrap(), "1\n0\n1\n2\n");
+    }
+
+    #[test]
+    fn if_else_script() {
+        let script = "
+            vicer note 14;
+            si note >= 10 {
+                vicer mention 1;
+                si note >= 16 { zipette 3; } sinon { zipette 2; };
+            } sinon {
+                vicer mention 0;
+            };
+            zipette mention;
+            si note < 10 { zipette 100; };
+            si faux { zipette 1; } sinon si vrai { zipette 4; };
+        ";
+        assert_eq!(run(script).unwrap(), "2\n1\n4\n");
+    }
+
+    #[test]
+    fn while_loop_sums_one_to_ten() {
+        assert_eq!(run("vicer i 1; vicer total 0; tantque i <= 10 { total += i; i++; }; zipette total;").unwrap(), "55\n");
+        assert_eq!(run("tantque faux { zipette 1; }; zipette 2;").unwrap(), "2\n");
+    }
+
+    #[test]
+    fn runaway_loop_hits_the_iteration_limit() {
+        let run = |source| run_with(source, |interpreter| interpreter.with_max_iterations(100));
+        assert_eq!(run("vicer i 0; tantque vrai { i++; };").unwrap_err().to_string(),
+            "[EXECUTION] Error : loop stopped after 100 iterations, the maximum allowed");
+        assert_eq!(run("vicer i 0; tantque i < 100 { i++; }; zipette i;").unwrap(), "100\n");
+    }
+
+    #[test]
+    fn repeat_loops() {
+        assert_eq!(run("boucle 3 { zipette indice; }; zipette 9;").unwrap(), "0\n1\n2\n9\n");
+        assert_eq!(run("boucle 2.9 { zipette 1; }; boucle 0 { zipette 2; };").unwrap(), "1\n1\n");
+        assert_eq!(run("boucle 1 { }; zipette indice;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable indice");
+        assert_eq!(run("boucle -1 { };").unwrap_err().to_string(), "[EXECUTION] Error : 'boucle' needs a positive number of iterations, got -1");
+        assert_eq!(run("boucle 0 / 0 { };").unwrap_err().to_string(), "[EXECUTION] Error : 'boucle' needs a positive number of iterations, got NaN");
+    }
+
+    #[test]
+    fn nested_repeat_multiplication_table_row() {
+        let script = "
+            boucle 2 {
+                vicer ligne indice + 1;
+                boucle 4 { zipette ligne * (indice + 1); };
+                zipette indice;
+            };
+        ";
+        assert_eq!(run(script).unwrap(), "1\n2\n3\n4\n0\n2\n4\n6\n8\n1\n");
+    }
+
+    #[test]
+    fn block_scopes() {
+        assert_eq!(run("vicer x 1; { x += 1; vicer y 5; zipette x + y; }; zipette x;").unwrap(), "7\n2\n");
+        assert_eq!(run("{ vicer local 3; }; zipette local;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable local");
+        assert_eq!(run("{ vicer a 1; { vicer b a + 1; vicer a b * 10; }; zipette a; zipette b; };").unwrap_err().to_string(),
+            "[EXECUTION] Error : use of undefined variable b");
+    }
+
+    #[test]
+    fn inner_loop_index_shadows_the_outer_one() {
+        let script = "boucle 2 { { vicer vu indice; boucle 1 { zipette indice; }; zipette vu + indice; }; };";
+        assert_eq!(run(script).unwrap(), "0\n0\n0\n2\n");
+    }
+
+    #[test]
+    fn function_calls() {
+        assert_eq!(run("fonction aire(l, h) { renvoie l * h; }; zipette aire(3, 4);").unwrap(), "12\n");
+        assert_eq!(run("fonction rien() { vicer x 1; }; zipette rien();").unwrap(), "0\n");
+        assert_eq!(run("fonction f() { renvoie 1; zipette 2; }; zipette f();").unwrap(), "1\n");
+        assert_eq!(run("zipette aire(1);").unwrap_err().to_string(), "[EXECUTION] Error : call to undefined function 'aire'");
+        assert_eq!(run("fonction aire(l, h) { renvoie l * h; }; zipette aire(1, 2, 3);").unwrap_err().to_string(),
+            "[EXECUTION] Error : function 'aire' expects 2 argument(s), got 3");
+    }
+
+    #[test]
+    fn default_parameters() {
+        assert_eq!(run("fonction aire(l, h = l) { renvoie l * h; }; zipette aire(3); zipette aire(3, 4);").unwrap(), "9\n12\n");
+        assert_eq!(run("fonction f(a = 1, b = a + 1) { renvoie [a, b]; }; zipette f(); zipette f(5); zipette f(5, 0);").unwrap(),
+            "[1, 2]\n[5, 6]\n[5, 0]\n");
+        // A default sees the parameters, not a global of the same name.
+        assert_eq!(run("vicer h 100; fonction f(l, h = l) { renvoie h; }; zipette f(2); zipette h;").unwrap(), "2\n100\n");
+        assert_eq!(run("fonction aire(l, h = l) { renvoie l * h; }; zipette aire();").unwrap_err().to_string(),
+            "[EXECUTION] Error : function 'aire' expects 1 to 2 argument(s), got 0");
+        assert_eq!(run("fonction aire(l, h = l) { renvoie l * h; }; zipette aire(1, 2, 3);").unwrap_err().to_string(),
+            "[EXECUTION] Error : function 'aire' expects 1 to 2 argument(s), got 3");
+    }
+
+    #[test]
+    fn forget_variables() {
+        assert_eq!(run("vicer x 1; oublie x; zipette x;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable x");
+        assert_eq!(run("vicer x 1; oublie x; vicer x 2; zipette x;").unwrap(), "2\n");
+        // Only the closest `x` goes away.
+        assert_eq!(run("vicer x 1; fonction f(x) { oublie x; renvoie x; }; zipette f(2); zipette x;").unwrap(), "1\n1\n");
+        assert_eq!(run("oublie x;").unwrap_err().to_string(), "[EXECUTION] Error : cannot forget undefined variable x");
+        assert_eq!(run("fixe x 1; oublie x;").unwrap_err().to_string(), "[EXECUTION] Error : cannot forget constant 'x'");
+    }
+
+    #[test]
+    fn imports_run_in_the_including_environment() {
+        let dir = write_files("import", &[
+            ("main.zipette", "importe \"lib/constantes.zipette\"; zipette double(N); zipette x;"),
+            ("lib/constantes.zipette", "fixe N 21; importe \"fonctions.zipette\"; vicer x 1; renvoie 0; vicer x 2;"),
+            ("lib/fonctions.zipette", "fonction double(n) { renvoie n * 2; };"),
+        ]);
+        assert_eq!(run_file(&dir.join("main.zipette")).unwrap(), "42\n1\n");
+    }
+
+    #[test]
+    fn import_errors() {
+        let dir = write_files("import-errors", &[
+            ("a.zipette", "importe \"b.zipette\";"),
+            ("b.zipette", "importe \"a.zipette\";"),
+            ("manquant.zipette", "zipette 1; importe \"rien.zipette\";"),
+            ("invalide.zipette", "importe \"erreur.zipette\";"),
+            ("erreur.zipette", "zipette 1;\nzipette 1 +;"),
+            ("lexique.zipette", "zipette 1 @;"),
+            ("lexique-importe.zipette", "importe \"lexique.zipette\";"),
+            ("tabulation.zipette", "\tzipette 1 +;"),
+            ("tabulation-importe.zipette", "importe \"tabulation.zipette\";"),
+        ]);
+        let path = |name: &str| dir.join(name).display().to_string();
+        assert_eq!(run_file(&dir.join("a.zipette")).unwrap_err().to_string(),
+            format!("[EXECUTION] Error : circular import: {} -> {} -> {}", path("a.zipette"), path("b.zipette"), path("a.zipette")));
+        assert!(run_file(&dir.join("manquant.zipette")).unwrap_err().to_string()
+            .starts_with(&format!("[EXECUTION] Error : cannot import '{}': ", path("rien.zipette"))));
+        assert_eq!(run_file(&dir.join("invalide.zipette")).unwrap_err().to_string(),
+            format!("[EXECUTION] Error : in '{}': [PARSER] Error at line 2, column 12: Expected an expression, found ';'", path("erreur.zipette")));
+        assert_eq!(run_file(&dir.join("lexique-importe.zipette")).unwrap_err().to_string(),
+            format!("[EXECUTION] Error : in '{}': [PARSER] Error at line 1, column 11: LexerError: Unexpected character '@'", path("lexique.zipette")));
+        // Tabs count like in the program that imports the file.
+        let importer = dir.join("tabulation-importe.zipette");
+        assert_eq!(run_file(&importer).unwrap_err().to_string(),
+            format!("[EXECUTION] Error : in '{}': [PARSER] Error at line 1, column 13: Expected an expression, found ';'", path("tabulation.zipette")));
+        assert_eq!(run_with(&std::fs::read_to_string(&importer).unwrap(), |interpreter| interpreter.with_path(&importer).with_tab_width(4)).unwrap_err().to_string(),
+            format!("[EXECUTION] Error : in '{}': [PARSER] Error at line 1, column 16: Expected an expression, found ';'", path("tabulation.zipette")));
+    }
+
+    #[test]
+    fn comments_do_not_change_the_program() {
+        let source = "# début\nvicer x 1; # un\nboucle 2 { /* double */ x *= 2;\n# fin de boucle\n};\nzipette x; # fin";
+        let program = Parser::new(Lexer::new(source).with_comments()).parse().unwrap();
+        assert_eq!(run_program(program).unwrap(), run(source).unwrap());
+    }
+
+    #[test]
+    fn function_scopes() {
+        // Parameters and locals stay inside the call; globals stay visible.
+        assert_eq!(run("vicer g 10; fonction f(x) { vicer y 1; renvoie x + y + g; }; zipette f(2); zipette y;").unwrap_err().to_string(),
+            "[EXECUTION] Error : use of undefined variable y");
+        assert_eq!(run("vicer g 10; fonction f(x) { vicer g x; renvoie 0; }; f(3); zipette g;").unwrap(), "3\n");
+        // The caller's block locals are not visible to the callee.
+        assert_eq!(run("fonction f() { renvoie local; }; { vicer local 1; zipette f(); };").unwrap_err().to_string(),
+            "[EXECUTION] Error : use of undefined variable local");
+        assert_eq!(run("fonction f(x) { renvoie x; }; { vicer x 1; zipette f(2); zipette x; };").unwrap(), "2\n1\n");
+    }
+
+    #[test]
+    fn recursive_functions() {
+        let script = "
+            fonction factorielle(n) {
+                si n <= 1 { renvoie 1; };
+                renvoie n * factorielle(n - 1);
+            };
+            zipette factorielle(5);
+            zipette factorielle(10);
+        ";
+        assert_eq!(run(script).unwrap(), "120\n3628800\n");
+        assert_eq!(run("fonction sans_fin(n) { renvoie sans_fin(n + 1); }; sans_fin(0);").unwrap_err().to_string(),
+            format!("[EXECUTION] Error : call to 'sans_fin' stopped after {} nested calls, the maximum allowed", MAX_CALL_DEPTH));
+    }
+
+    #[test]
+    fn return_unwinds_loops() {
+        let script = "fonction premier(n) { vicer i 0; tantque 1 { boucle 10 { si indice == n { renvoie i; }; i++; }; }; }; zipette premier(4);";
+        assert_eq!(run(script).unwrap(), "4\n");
+    }
+
+    #[test]
+    fn top_level_return_stops_the_program() {
+        let script = "boucle 5 { zipette indice; si indice == 2 { renvoie indice * 10; }; }; zipette 99;";
+        let (outcome, output) = output_of(parse(script), Interpreter::run);
+        assert_eq!(outcome.unwrap(), Outcome::Returned(Value::Number(20.0)));
+        assert_eq!(output, "0\n1\n2\n");
+
+        assert_eq!(Interpreter::with_output(parse("zipette 1;"), Box::new(io::sink())).run().unwrap(), Outcome::Returned(Value::Number(0.0)));
+        assert_eq!(Interpreter::with_output(parse("{ tantque 1 { renvoie 7; }; };"), Box::new(io::sink())).run().unwrap(), Outcome::Returned(Value::Number(7.0)));
+    }
+
+    #[test]
+    fn break_and_continue() {
+        assert_eq!(run("vicer i 0; tantque 1 { i++; zipette i; si i == 3 { casse; }; }; zipette 99;").unwrap(), "1\n2\n3\n99\n");
+        assert_eq!(run("boucle 6 { si indice % 2 == 0 { continue; }; zipette indice; };").unwrap(), "1\n3\n5\n");
+        let script = "boucle 2 { vicer ligne indice; boucle 5 { si indice == 2 { casse; }; zipette ligne * 10 + indice; }; };";
+        assert_eq!(run(script).unwrap(), "0\n1\n10\n11\n");
+        assert_eq!(run("vicer i 0; tantque i < 4 { i++; { si i == 2 { continue; }; }; zipette i; };").unwrap(), "1\n3\n4\n");
+    }
+
+    #[test]
+    fn string_values() {
+        assert_eq!(run("vicer nom \"monde\"; zipette \"bonjour \" + nom;").unwrap(), "bonjour monde\n");
+        assert_eq!(run("zipette \"n = \" + 1.5; zipette 2 + \" fois\"; zipette 1 + 2 + \"!\";").unwrap(), "n = 1.5\n2 fois\n3!\n");
+        assert_eq!(run("zipette \"a\" == \"a\"; zipette \"a\" != \"b\"; zipette \"1\" == 1;").unwrap(), "1\n1\n0\n");
+        assert_eq!(run("si \"\" { zipette 1; } sinon { zipette 2; };").unwrap(), "2\n");
+        assert_eq!(run("lsd red \"rouge\";").unwrap(), format!("{}\n", "rouge".red()));
+    }
+
+    #[test]
+    fn string_operator_errors() {
+        assert_eq!(run("zipette \"a\" * 2;").unwrap_err().to_string(), "[EXECUTION] Error : cannot apply '*' to texte and nombre");
+        assert_eq!(run("zipette 1 - \"a\";").unwrap_err().to_string(), "[EXECUTION] Error : cannot apply '-' to nombre and texte");
+        assert_eq!(run("zipette \"a\" < \"b\";").unwrap_err().to_string(), "[EXECUTION] Error : cannot apply '<' to texte and texte");
+        assert_eq!(run("zipette -\"a\";").unwrap_err().to_string(), "[EXECUTION] Error : cannot apply '-' to texte");
+        assert_eq!(run("boucle \"3\" { };").unwrap_err().to_string(), "[EXECUTION] Error : 'boucle' needs a number of iterations, got texte");
+        assert_eq!(run("vicer x: nombre \"3\";").unwrap_err().to_string(), "[EXECUTION] Error : 'x' is declared as nombre but was given texte");
+    }
+
+    #[test]
+    fn array_values() {
+        assert_eq!(run("vicer tableau [1, 2, 3]; zipette tableau; zipette tableau[0] + tableau[2];").unwrap(), "[1, 2, 3]\n4\n");
+        assert_eq!(run("vicer t [[1, 2], [3]]; zipette t; zipette t[0][1]; zipette t[1]; zipette longueur(t[0]);").unwrap(), "[[1, 2], [3]]\n2\n[3]\n2\n");
+        assert_eq!(run("zipette [\"a\", 1 + 1, []]; zipette longueur([]); zipette longueur(\"été\");").unwrap(), "[\"a\", 2, []]\n0\n3\n");
+        assert_eq!(run("zipette [1, 2] == [1, 2]; zipette \"t = \" + [1];").unwrap(), "1\nt = [1]\n");
+    }
+
+    #[test]
+    fn variadic_builtins() {
+        assert_eq!(run("zipette somme(); zipette produit();").unwrap(), "0\n1\n");
+        assert_eq!(run("zipette somme(4); zipette produit(4);").unwrap(), "4\n4\n");
+        assert_eq!(run("zipette somme(1, 2, 3, 4, 5, 6); zipette produit(1, 2, 3, 4, 5, 6);").unwrap(), "21\n720\n");
+        assert_eq!(run("zipette somme(1, produit(2, 3)); zipette produit(somme(), 5);").unwrap(), "7\n0\n");
+        assert_eq!(run("zipette somme(1, \"2\");").unwrap_err().to_string(), "[EXECUTION] Error : 'somme' expects nombre arguments, got texte");
+    }
+
+    fn run_seeded(source: &str, seed: u64) -> String {
+        run_with(source, |interpreter| interpreter.with_seed(seed)).unwrap()
+    }
+
+    #[test]
+    fn seeded_random_numbers_repeat() {
+        let script = "boucle 5 { zipette hasard, hasard(-10, 10); }; lsd multi \"couleurs\";";
+        let first = run_seeded(script, 42);
+        assert_eq!(first, run_seeded(script, 42));
+        assert_ne!(first, run_seeded(script, 43));
+    }
+
+    #[test]
+    fn random_numbers_stay_in_range() {
+        let script = "
+            boucle 1000 {
+                vicer x hasard;
+                verifie x >= 0 && x < 1;
+                vicer y hasard(-3, 2.5);
+                verifie y >= -3 && y < 2.5;
+            };
+            zipette hasard(4, 4);
+        ";
+        assert_eq!(run_seeded(script, 7), "4\n");
+        assert_eq!(run("zipette hasard(2, 1);").unwrap_err().to_string(),
+            "[EXECUTION] Error : 'hasard' needs a minimum no greater than its maximum, got 2 and 1");
+        assert_eq!(run("zipette hasard(0, \"1\");").unwrap_err().to_string(), "[EXECUTION] Error : 'hasard' expects nombre bounds, got texte");
+    }
+
+    #[test]
+    fn clock_with_a_fake_time() {
+        // Each reading moves the fake clock forward by 5 ms.
+        let now = Rc::new(RefCell::new(0.0));
+        let clock = Rc::clone(&now);
+        let output = run_with("vicer debut horloge; boucle 3 { zipette horloge - debut; };", |interpreter| interpreter.with_clock(move || {
+            *clock.borrow_mut() += 5.0;
+            *clock.borrow()
+        }));
+        assert_eq!(output.unwrap(), "5\n10\n15\n");
+        assert_eq!(*now.borrow(), 20.0);
+    }
+
+    #[test]
+    fn real_clock_does_not_go_back() {
+        assert_eq!(run("vicer avant horloge; verifie avant >= 0; boucle 100 { vicer t horloge; verifie t >= avant; vicer avant t; };").unwrap(), "");
+    }
+
+    #[test]
+    fn min_and_max_builtins() {
+        assert_eq!(run("zipette max(1, 2**3, 5); zipette min(1, 2**3, -5); zipette min(4, 4);").unwrap(), "8\n-5\n4\n");
+        assert_eq!(run("zipette min(1, 0 / 0, -5); zipette max(0 / 0, 1);").unwrap(), "NaN\nNaN\n");
+        assert_eq!(run("zipette min();").unwrap_err().to_string(), "[EXECUTION] Error : function 'min' expects at least 2 argument(s), got 0");
+        assert_eq!(run("zipette max(1);").unwrap_err().to_string(), "[EXECUTION] Error : function 'max' expects at least 2 argument(s), got 1");
+        assert_eq!(run("zipette max(1, [2]);").unwrap_err().to_string(), "[EXECUTION] Error : 'max' expects nombre arguments, got tableau");
+    }
+
+    #[test]
+    fn abs_and_signe_builtins() {
+        assert_eq!(run("zipette abs(-3), abs(2.5), abs(-0), abs(0 / 0);").unwrap(), "3 2.5 0 NaN\n");
+        assert_eq!(run("zipette signe(-3), signe(2.5), signe(0), signe(-0), signe(0 / 0);").unwrap(), "-1 1 0 0 NaN\n");
+        assert_eq!(run("vicer a 1.0004; vicer b 1; zipette abs(a - b) < 0.001, signe(b - a) * 2;").unwrap(), "1 -2\n");
+        assert_eq!(run("zipette abs();").unwrap_err().to_string(), "[EXECUTION] Error : function 'abs' expects 1 argument(s), got 0");
+        assert_eq!(run("zipette signe(\"1\");").unwrap_err().to_string(), "[EXECUTION] Error : 'signe' expects nombre arguments, got texte");
+    }
+
+    #[test]
+    fn racine_builtin() {
+        assert_eq!(run("zipette racine(2), racine(16), racine(0), racine(0.25);").unwrap(), "1.4142135623730951 4 0 0.5\n");
+        assert_eq!(run("zipette racine(27, 3), racine(-8, 3), racine(16, 4), racine(2, 0.5);").unwrap(), "3 -2 2 4\n");
+        assert_eq!(run("zipette racine(-4);").unwrap_err().to_string(), "[EXECUTION] Error : 'racine' cannot take root 2 of the negative number -4");
+        assert_eq!(run("zipette racine(-16, 4);").unwrap_err().to_string(), "[EXECUTION] Error : 'racine' cannot take root 4 of the negative number -16");
+        assert_eq!(run("zipette racine(8, 0);").unwrap_err().to_string(), "[EXECUTION] Error : 'racine' cannot take root 0 of a number");
+        assert_eq!(run("zipette racine();").unwrap_err().to_string(), "[EXECUTION] Error : wrong number of arguments to 'racine': expected 1 or 2, got 0");
+    }
+
+    #[test]
+    fn rounding_builtins() {
+        assert_eq!(run("zipette plancher(2.7), plancher(-2.2), plafond(2.2), plafond(-2.7), tronque(2.7), tronque(-2.7);").unwrap(),
+            "2 -3 3 -2 2 -2\n");
+        assert_eq!(run("zipette arrondi(2.5), arrondi(-2.5), arrondi(2.4), arrondi(7);").unwrap(), "3 -3 2 7\n");
+        // Scale and round: 2.675 * 100 is 267.5, but 1.005 * 100 is 100.49999999999999.
+        assert_eq!(run("zipette arrondi(2.675, 2), arrondi(1.005, 2), arrondi(3.14159, 3), arrondi(0.125, 2);").unwrap(), "2.68 1 3.142 0.13\n");
+        assert_eq!(run("zipette arrondi(1234.5, -1), arrondi(1250, -2), arrondi(-1234, -3), arrondi(12, -2);").unwrap(), "1230 1300 -1000 0\n");
+        assert_eq!(run("zipette arrondi(1, 2, 3);").unwrap_err().to_string(), "[EXECUTION] Error : wrong number of arguments to 'arrondi': expected 1 or 2, got 3");
+        assert_eq!(run("zipette arrondi(1, 0.5);").unwrap_err().to_string(), "[EXECUTION] Error : 'arrondi' needs a whole number of decimal places, got 0.5");
+        assert_eq!(run("zipette plancher(1, 2);").unwrap_err().to_string(), "[EXECUTION] Error : function 'plancher' expects 1 argument(s), got 2");
+    }
+
+    #[test]
+    fn trigonometry_builtins() {
+        assert_eq!(run("zipette sin(0), cos(0), tan(0), sin(PI / 2), cos(PI);").unwrap(), "0 1 0 1 -1\n");
+        assert_eq!(run("zipette sind(90), sind(180), sind(-90), cosd(90), cosd(180), cosd(720), tand(45), tand(-180);").unwrap(), "1 0 -1 0 -1 1 0.9999999999999999 0\n");
+        assert_eq!(run("zipette sind(30), cosd(60);").unwrap(), "0.49999999999999994 0.5000000000000001\n");
+        assert_eq!(run("zipette asin(1), acos(1), atan(1) * 4, atan2(1, 1), PI / 4, atan2(0, -1);").unwrap(),
+            "1.5707963267948966 0 3.141592653589793 0.7853981633974483 0.7853981633974483 3.141592653589793\n");
+        assert_eq!(run("zipette asin(1.5);").unwrap_err().to_string(), "[EXECUTION] Error : 'asin' needs a number between -1 and 1, got 1.5");
+        assert_eq!(run("zipette acos(-2);").unwrap_err().to_string(), "[EXECUTION] Error : 'acos' needs a number between -1 and 1, got -2");
+        assert_eq!(run("zipette tand(90);").unwrap_err().to_string(), "[EXECUTION] Error : 'tand' is not defined for 90");
+        assert_eq!(run("zipette atan2(1);").unwrap_err().to_string(), "[EXECUTION] Error : function 'atan2' expects 2 argument(s), got 1");
+    }
+
+    #[test]
+    fn predefined_constants() {
+        assert_eq!(run("zipette PI, E;").unwrap(), "3.141592653589793 2.718281828459045\n");
+        assert_eq!(run("vicer PI 3;").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'PI'");
+        assert_eq!(run("fixe E 2;").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'E'");
+        assert_eq!(run("oublie PI;").unwrap_err().to_string(), "[EXECUTION] Error : cannot forget constant 'PI'");
+    }
+
+    #[test]
+    fn logarithm_builtins() {
+        assert_eq!(run("zipette log(1), log(E), exp(0), exp(1), log(exp(2));").unwrap(), "0 1 1 2.718281828459045 2\n");
+        assert_eq!(run("zipette log2(1 << 10), log2(8), log10(1000), log10(0.001), log2(3);").unwrap(), "10 3 3 -3 1.584962500721156\n");
+        assert_eq!(run("zipette log(1000, 10), log(8, 2), log(81, 3), log(243, 3), log(0.25, 0.5), log(10, 3);").unwrap(),
+            "3 3 4 5 2 2.095903274289385\n");
+        assert_eq!(run("zipette log(0);").unwrap_err().to_string(), "[EXECUTION] Error : 'log' needs a positive number, got 0");
+        assert_eq!(run("zipette log(-1, 2);").unwrap_err().to_string(), "[EXECUTION] Error : 'log' needs a positive number, got -1");
+        assert_eq!(run("zipette log2(-8);").unwrap_err().to_string(), "[EXECUTION] Error : 'log2' needs a positive number, got -8");
+        assert_eq!(run("zipette log10(0);").unwrap_err().to_string(), "[EXECUTION] Error : 'log10' needs a positive number, got 0");
+        assert_eq!(run("zipette log(8, 1);").unwrap_err().to_string(), "[EXECUTION] Error : 'log' needs a positive base other than 1, got 1");
+        assert_eq!(run("zipette log(8, 0);").unwrap_err().to_string(), "[EXECUTION] Error : 'log' needs a positive base other than 1, got 0");
+        assert_eq!(run("zipette log(8, -2);").unwrap_err().to_string(), "[EXECUTION] Error : 'log' needs a positive base other than 1, got -2");
+        assert_eq!(run("zipette exp();").unwrap_err().to_string(), "[EXECUTION] Error : function 'exp' expects 1 argument(s), got 0");
+    }
+
+    #[test]
+    fn typede_builtin() {
+        assert_eq!(run("zipette typede(1), typede(\"a\"), typede([1]), typede(vrai), typede(\"${1}\");").unwrap(), "nombre texte tableau nombre texte\n");
+        assert_eq!(run("vicer x 2; si typede(x) == \"nombre\" { zipette \"ok\"; }; zipette typede(typede(x));").unwrap(), "ok\ntexte\n");
+        assert_eq!(run("zipette typede(x);").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable x");
+        assert_eq!(run("zipette typede();").unwrap_err().to_string(), "[EXECUTION] Error : function 'typede' expects 1 argument(s), got 0");
+    }
+
+    #[test]
+    fn equality_across_types() {
+        assert_eq!(run("zipette 1 == \"1\", 1 != \"1\", [1] == 1, \"\" == 0, [] == \"\";").unwrap(), "0 1 0 0 0\n");
+        assert_eq!(run("zipette \"a\" == \"a\", \"a\" == \"A\", [1, \"a\"] == [1, \"a\"], [1] == [\"1\"];").unwrap(), "1 0 1 0\n");
+    }
+
+    #[test]
+    fn conversion_builtins() {
+        assert_eq!(run("zipette nombre(\"3,5\") + 1, nombre(\"3.5\"), nombre(\" -1_000 \"), nombre(\"0x1F\"), nombre(vrai), nombre(faux), nombre(2);").unwrap(),
+            "4.5 3.5 -1000 31 1 0 2\n");
+        assert_eq!(run("zipette nombre(\"trois\");").unwrap_err().to_string(), "[EXECUTION] Error : 'nombre' cannot read a number from 'trois'");
+        assert_eq!(run("zipette nombre(\"3 4\");").unwrap_err().to_string(), "[EXECUTION] Error : 'nombre' cannot read a number from '3 4'");
+        assert_eq!(run("zipette nombre([1]);").unwrap_err().to_string(), "[EXECUTION] Error : 'nombre' expects a nombre or a texte, got tableau");
+        assert_eq!(run("zipette texte(3.5) + \"!\", longueur(texte(1 / 3)), texte([1, \"a\", [2]]), texte(\"a\");").unwrap(),
+            "3.5! 18 [1, \"a\", [2]] a\n");
+        // What `zipette` prints reads back as the same number.
+        assert_eq!(run("vicer x 1 / 3; zipette nombre(texte(x)) == x, nombre(texte(-0.1)) == -0.1;").unwrap(), "1 1\n");
+    }
+
+    #[test]
+    fn script_arguments() {
+        let args = ["3", "4,5", "x"].map(String::from).to_vec();
+        assert_eq!(run_with("zipette arg1 + arg2, arg3, nbargs, arguments;", |interpreter| interpreter.with_args(args)).unwrap(),
+            "7.5 x 3 [3, 4.5, \"x\"]\n");
+        assert_eq!(run("zipette arg1;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable arg1");
+    }
+
+    #[test]
+    fn array_errors() {
+        assert_eq!(run("zipette [1, 2, 3][3];").unwrap_err().to_string(), "[EXECUTION] Error : index 3 is out of range for an array of length 3");
+        assert_eq!(run("zipette [1, 2, 3][-1];").unwrap_err().to_string(), "[EXECUTION] Error : index -1 is out of range for an array of length 3");
+        assert_eq!(run("zipette [1][0.5];").unwrap_err().to_string(), "[EXECUTION] Error : index 0.5 is out of range for an array of length 1");
+        assert_eq!(run("zipette [1][\"0\"];").unwrap_err().to_string(), "[EXECUTION] Error : an index must be a nombre, got texte");
+        assert_eq!(run("vicer x 1; zipette x[0];").unwrap_err().to_string(), "[EXECUTION] Error : cannot index a nombre");
+        assert_eq!(run("zipette [1] * 2;").unwrap_err().to_string(), "[EXECUTION] Error : cannot apply '*' to tableau and nombre");
+        assert_eq!(run("zipette longueur(1);").unwrap_err().to_string(), "[EXECUTION] Error : 'longueur' expects a tableau or a texte, got nombre");
+        assert_eq!(run("zipette longueur([], []);").unwrap_err().to_string(), "[EXECUTION] Error : function 'longueur' expects 1 argument(s), got 2");
+    }
+
+    #[test]
+    fn lsd_with_a_computed_color() {
+        assert_eq!(run("vicer c 0; lsd (c) 5;").unwrap(), format!("{}\n", "5".red()));
+        assert_eq!(run("vicer c 1; lsd (c) -5;").unwrap(), format!("{}\n", "-5".blue()));
+        assert_eq!(run("fonction couleur(n) { renvoie n % 11; }; lsd couleur(13) 7;").unwrap(), format!("{}\n", "7".green()));
+        assert_eq!(run("lsd 9 \"rose\";").unwrap(), format!("{}\n", "rose".custom_color((255, 20, 147))));
+        assert_eq!(run("lsd 11 1;").unwrap_err().to_string(), "[EXECUTION] Error : a color must be a number from 0 to 10, got 11");
+        assert_eq!(run("lsd (1.5) 1;").unwrap_err().to_string(), "[EXECUTION] Error : a color must be a number from 0 to 10, got 1.5");
+        assert_eq!(run("lsd \"red\" 1;").unwrap_err().to_string(), "[EXECUTION] Error : a color must be a number from 0 to 10, got red");
+    }
+
+    #[test]
+    fn lsd_with_rgb_components() {
+        assert_eq!(run("lsd rgb(255, 100, 0) 42;").unwrap(), format!("{}\n", "42".custom_color((255, 100, 0))));
+        assert_eq!(run("vicer x 10; lsd rgb(x, x * 2, 0) \"a\";").unwrap(), format!("{}\n", "a".custom_color((10, 20, 0))));
+        assert_eq!(run("lsd rgb(256, 0, 0) 1;").unwrap_err().to_string(), "[EXECUTION] Error : an rgb component must be a number from 0 to 255, got 256");
+        assert_eq!(run("lsd rgb(0, -1, 0) 1;").unwrap_err().to_string(), "[EXECUTION] Error : an rgb component must be a number from 0 to 255, got -1");
+        assert_eq!(run("lsd rgb(0, 0, 0.5) 1;").unwrap_err().to_string(), "[EXECUTION] Error : an rgb component must be a number from 0 to 255, got 0.5");
+    }
+
+    #[test]
+    fn print_several_values() {
+        assert_eq!(run("vicer a 3; zipette a, a + 4, a * 4;").unwrap(), "3 7 12\n");
+        assert_eq!(run("zipette 1, (2 + 3), \"six\", [7], (8 * 1);").unwrap(), "1 5 six [7] 8\n");
+        assert_eq!(run("lsd green 1, 2;").unwrap(), format!("{}\n", "1 2".green()));
+    }
+
+    #[test]
+    fn write_without_newline() {
+        assert_eq!(run("ecrit 1; ecrit \"a\", 2;").unwrap(), "1a 2");
+        assert_eq!(run("boucle 3 { ecrit \"#\"; }; zipette; zipette \"fini\";").unwrap(), "###\nfini\n");
+        assert_eq!(run("zipette;").unwrap(), "\n");
+    }
+
+    #[test]
+    fn input_reads_numbers() {
+        let run_with_input = |source, input: &'static str| run_with(source, |interpreter| interpreter.with_input(Box::new(input.as_bytes())));
+        assert_eq!(run_with_input("demande x; demande y; zipette x + y;", "2\n3,5\n").unwrap(), "5.5\n");
+        assert_eq!(run_with_input("demande x \"Entrez un nombre: \"; zipette x * 2;", "-4\r\n").unwrap(), "Entrez un nombre: -8\n");
+        assert_eq!(run_with_input("demande x;", "douze\n").unwrap_err().to_string(), "[EXECUTION] Error : expected a number for 'x', got 'douze'");
+        assert_eq!(run_with_input("demande x; demande y;", "1\n").unwrap_err().to_string(), "[EXECUTION] Error : no input left to read into 'y'");
+    }
+
+    #[test]
+    fn environments_leave_stdin_unlocked() {
+        let _interpreter = Interpreter::new(Vec::new());
+        let (locked, receiver) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            let _lock = io::stdin().lock();
+            locked.send(()).unwrap();
+        });
+        receiver.recv_timeout(Duration::from_secs(5)).expect("stdin should not be held by an environment");
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(run("fixe G 3.14159; fixe TAU G * 2; zipette TAU; vicer r 2; zipette G * r * r;").unwrap(), "6.28318\n12.56636\n");
+        assert_eq!(run("fixe G 3; vicer G 4;").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'G'");
+        assert_eq!(run("fixe G 3; fixe G 4;").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'G'");
+        assert_eq!(run("fixe N 1; N += 1;").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'N'");
+        assert_eq!(run("fixe N 1; { vicer N 2; };").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'N'");
+        assert_eq!(run("fixe indice 1; boucle 2 { };").unwrap_err().to_string(), "[EXECUTION] Error : cannot reassign constant 'indice'");
+        // A parameter is a new variable, even when a constant has its name.
+        assert_eq!(run("fixe x 1; fonction f(x) { renvoie x * 10; }; zipette f(2), x;").unwrap(), "20 1\n");
+        // A variable can be frozen, and a block's constant goes away with it.
+        assert_eq!(run("vicer x 1; fixe x x + 1; zipette x; { fixe y 1; }; vicer y 2; zipette y;").unwrap(), "2\n2\n");
+    }
+
+    #[test]
+    fn assertions() {
+        assert_eq!(run("vicer x 5; verifie x == 5; verifie x; verifie vrai; zipette 1;").unwrap(), "1\n");
+        assert_eq!(run("vicer x 3; verifie x == 5; zipette 1;").unwrap_err().to_string(), "[EXECUTION] Error : assertion failed: x == 5 (x = 3)");
+        assert_eq!(run("vicer a 1; vicer b 2; verifie a + 1 > b * 2;").unwrap_err().to_string(),
+            "[EXECUTION] Error : assertion failed: a + 1 > b * 2 (a + 1 = 2, b * 2 = 4)");
+        assert_eq!(run("vicer zero 0; verifie zero;").unwrap_err().to_string(), "[EXECUTION] Error : assertion failed: zero (zero = 0)");
+        assert_eq!(run("verifie 1 > 2;").unwrap_err().to_string(), "[EXECUTION] Error : assertion failed: 1 > 2");
+        assert_eq!(run("verifie longueur([]);").unwrap_err().to_string(), "[EXECUTION] Error : assertion failed: longueur([])");
+        assert_eq!(run("verifie y == 1;").unwrap_err().to_string(), "[EXECUTION] Error : use of undefined variable y");
+    }
+
+    #[test]
+    fn exit_stops_the_program_with_a_code() {
+        let exit = |source| output_of(parse(source), Interpreter::interpret);
+        assert_eq!(exit("zipette 1; quitte 2; zipette 3;"), (2, "1\n".to_string()));
+        assert_eq!(exit("boucle 5 { si indice == 1 { quitte indice + 2.9; }; zipette indice; }; zipette 9;"), (3, "0\n".to_string()));
+        assert_eq!(exit("quitte;"), (0, String::new()));
+        assert_eq!(exit("zipette 1;"), (0, "1\n".to_string()));
+        assert_eq!(exit("renvoie 5;"), (0, String::new()));
+        assert_eq!(exit("fonction f(n) { si n > 2 { quitte n; }; renvoie f(n + 1); }; zipette f(0); zipette 1;"), (3, String::new()));
+        assert_eq!(exit("zipette y;").0, 1);
+        assert_eq!(exit("quitte 255;").0, 255);
+        assert_eq!(run("quitte \"a\";").unwrap_err().to_string(), "[EXECUTION] Error : 'quitte' needs a number as exit code, got texte");
+        assert_eq!(run("quitte 256;").unwrap_err().to_string(), "[EXECUTION] Error : 'quitte' needs an exit code between 0 and 255, got 256");
+        assert_eq!(run("quitte -1;").unwrap_err().to_string(), "[EXECUTION] Error : 'quitte' needs an exit code between 0 and 255, got -1");
+        assert_eq!(run("quitte 1e20;").unwrap_err().to_string(), "[EXECUTION] Error : 'quitte' needs an exit code between 0 and 255, got 100000000000000000000");
+        assert_eq!(run("quitte 0 / 0;").unwrap_err().to_string(), "[EXECUTION] Error : 'quitte' needs an exit code between 0 and 255, got NaN");
+    }
+
+    #[test]
+    fn sleep_durations() {
+        let sleeps = Rc::new(RefCell::new(Vec::new()));
+        let recorded = Rc::clone(&sleeps);
+        let output = run_with("vicer delai 250; attend 500; zipette 1; attend delai * 2; attend 0.5; attend 1e300;",
+            |interpreter| interpreter.with_sleep(move |duration| recorded.borrow_mut().push(duration)));
+        assert_eq!(output.unwrap(), "1\n");
+        assert_eq!(*sleeps.borrow(), [Duration::from_millis(500), Duration::from_millis(500), Duration::from_micros(500), Duration::MAX]);
+
+        assert_eq!(run("attend -1;").unwrap_err().to_string(), "[EXECUTION] Error : 'attend' needs a positive number of milliseconds, got -1");
+        assert_eq!(run("attend 0 / 0;").unwrap_err().to_string(), "[EXECUTION] Error : 'attend' needs a positive number of milliseconds, got NaN");
+        assert_eq!(run("attend \"1\";").unwrap_err().to_string(), "[EXECUTION] Error : 'attend' needs a number of milliseconds, got texte");
+    }
+
+    #[test]
+    fn stray_semicolons_are_empty_statements() {
+        assert_eq!(run(";;; zipette 1;;").unwrap(), "1\n");
+    }
+
+    #[test]
+    fn folded_programs_print_the_same() {
+        let source = "zipette 1 / 0, -1 / 0, 0 / 0, 5 % 0, 1 << 64, 1 << 1e20, 8 >> 99, 2 ** 1024, ~0, 3 < 4;";
+        let program = parse(source);
+        let mut folded = program.clone();
+        fold_constants(&mut folded);
+        assert!(matches!(&folded[0], Statement::Print(values) if values.iter().all(|value| matches!(value, Expression::Number(_)))));
+        assert_eq!(run_program(folded).unwrap(), run_program(program).unwrap());
+    }
+
+    #[test]
+    fn selon_runs_the_first_matching_arm() {
+        let program = |x: &str| format!("vicer x {}; selon x {{ 1: {{ zipette 10; }} 2 - 1: {{ zipette 11; }} 2: {{ zipette 20; }} autre: {{ zipette 0; }} }};", x);
+        assert_eq!(run(&program("1")).unwrap(), "10\n");
+        assert_eq!(run(&program("2")).unwrap(), "20\n");
+        assert_eq!(run(&program("3")).unwrap(), "0\n");
+        assert_eq!(run("selon \"a\" { 1: { zipette 1; } \"a\": { zipette 2; } };").unwrap(), "2\n");
+    }
+
+    #[test]
+    fn selon_without_match_nor_autre_does_nothing() {
+        assert_eq!(run("selon 3 { 1: { zipette 1; } 2: { zipette 2; } }; selon 3 { }; zipette \"fin\";").unwrap(), "fin\n");
+    }
+
+    #[test]
+    fn selon_evaluates_its_value_once() {
+        let source = "vicer n 0; fonction suivant() { n += 1; renvoie n; };
+            selon suivant() { 2: { zipette 2; } 1: { zipette 1; } }; zipette n;";
+        assert_eq!(run(source).unwrap(), "1\n1\n");
+    }
+
+    #[test]
+    fn fais_runs_its_body_at_least_once() {
+        assert_eq!(run("fais { zipette 1; } tantque 0;").unwrap(), "1\n");
+        assert_eq!(run("fais { vicer essai 5; } tantque essai < 3; zipette essai;").unwrap(), "5\n");
+    }
+
+    #[test]
+    fn fais_repeats_while_its_condition_holds() {
+        assert_eq!(run("vicer i 0; fais { i++; si i == 2 { continue; }; zipette i; } tantque i < 4;").unwrap(), "1\n3\n4\n");
+        assert_eq!(run("vicer i 0; fais { i++; si i == 3 { casse; }; } tantque vrai; zipette i;").unwrap(), "3\n");
+    }
+
+    #[test]
+    fn fais_counts_towards_the_iteration_limit() {
+        let err = run_with("fais { } tantque vrai;", |interpreter| interpreter.with_max_iterations(10)).unwrap_err();
+        assert_eq!(err.to_string(), "[EXECUTION] Error : loop stopped after 10 iterations, the maximum allowed");
+    }
+
+    #[test]
+    fn interpolated_strings_are_expressions() {
+        assert_eq!(run("vicer x 4; zipette \"x vaut ${x + 1} et ${(x - 1) * (2 + x)}\";").unwrap(), "x vaut 5 et 18\n");
+        assert_eq!(run("vicer x 2; vicer s \"${x}${x}\"; zipette s + \"!\", longueur(s);").unwrap(), "22! 2\n");
+        assert!(run("lsd rouge \"a${1}\", \"b\";").unwrap().contains("a1"));
     }
 }
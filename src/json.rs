@@ -0,0 +1,489 @@
+//! JSON form of the syntax tree, for tools that want to look at a program
+//! without linking against the interpreter.
+//!
+//! Every node is an object whose `"type"` is the name of its variant, the
+//! other keys being the variant's fields in declaration order. Optional
+//! fields are `null` when absent, and operators, colors and types are the
+//! names of their variants. Numbers that JSON cannot write, which come from
+//! literals too large for an `f64` (`1e400`) or from folding `1 / 0`, are
+//! the strings `"inf"`, `"-inf"` and `"NaN"`.
+
+use crate::parser::{Colored, Expression, Statement, StringPart};
+
+/// Writes `program` as a JSON array of statements.
+pub fn to_json(program: &[Statement]) -> String {
+    let mut out = String::new();
+    write_list(&mut out, program, write_statement);
+    out
+}
+
+/// An object being written, from its opening brace to `end`.
+struct Object<'a> {
+    out: &'a mut String,
+}
+
+impl<'a> Object<'a> {
+    fn new(out: &'a mut String, kind: &str) -> Self {
+        out.push_str("{\"type\":");
+        write_string(out, kind);
+        Self { out }
+    }
+
+    /// Writes the key of the next field, returning where to write its value.
+    fn field(&mut self, key: &str) -> &mut String {
+        self.out.push(',');
+        write_string(self.out, key);
+        self.out.push(':');
+        self.out
+    }
+
+    fn end(self) {
+        self.out.push('}');
+    }
+}
+
+fn write_statement(out: &mut String, statement: &Statement) {
+    match statement {
+        Statement::Expression(expr) => {
+            let mut object = Object::new(out, "Expression");
+            write_expression(object.field("expr"), expr);
+            object.end();
+        }
+        Statement::Print(values) => write_values(out, "Print", values),
+        Statement::Write(values) => write_values(out, "Write", values),
+        Statement::Input { name, prompt } => {
+            let mut object = Object::new(out, "Input");
+            write_string(object.field("name"), name);
+            write_option(object.field("prompt"), prompt.as_ref(), write_expression);
+            object.end();
+        }
+        Statement::PrintColored(color, name, values) => {
+            let mut object = Object::new(out, "PrintColored");
+            write_color(object.field("color"), color);
+            write_string(object.field("name"), name);
+            write_list(object.field("values"), values, write_expression);
+            object.end();
+        }
+        Statement::PrintColoredDyn(color, values) => {
+            let mut object = Object::new(out, "PrintColoredDyn");
+            write_expression(object.field("color"), color);
+            write_list(object.field("values"), values, write_expression);
+            object.end();
+        }
+        Statement::PrintColoredRgb(components, values) => {
+            let mut object = Object::new(out, "PrintColoredRgb");
+            write_list(object.field("components"), &**components, write_expression);
+            write_list(object.field("values"), values, write_expression);
+            object.end();
+        }
+        Statement::Assignment(name, value) => write_binding(out, "Assignment", name, value),
+        Statement::CompoundAssignment(name, op, value) => {
+            let mut object = Object::new(out, "CompoundAssignment");
+            write_string(object.field("name"), name);
+            write_string(object.field("op"), &format!("{:?}", op));
+            write_expression(object.field("value"), value);
+            object.end();
+        }
+        Statement::Step { name, op, prefix } => {
+            let mut object = Object::new(out, "Step");
+            write_string(object.field("name"), name);
+            write_string(object.field("op"), &format!("{:?}", op));
+            object.field("prefix").push_str(if *prefix { "true" } else { "false" });
+            object.end();
+        }
+        Statement::Constant(name, value) => write_binding(out, "Constant", name, value),
+        Statement::Forget(name) => {
+            let mut object = Object::new(out, "Forget");
+            write_string(object.field("name"), name);
+            object.end();
+        }
+        Statement::Import(file) => {
+            let mut object = Object::new(out, "Import");
+            write_string(object.field("file"), file);
+            object.end();
+        }
+        Statement::Assert(cond) => {
+            let mut object = Object::new(out, "Assert");
+            write_expression(object.field("cond"), cond);
+            object.end();
+        }
+        Statement::Exit(code) => {
+            let mut object = Object::new(out, "Exit");
+            write_option(object.field("code"), code.as_ref(), write_expression);
+            object.end();
+        }
+        Statement::Sleep(duration) => {
+            let mut object = Object::new(out, "Sleep");
+            write_expression(object.field("duration"), duration);
+            object.end();
+        }
+        Statement::TypedAssignment(name, value_type, value) => {
+            let mut object = Object::new(out, "TypedAssignment");
+            write_string(object.field("name"), name);
+            write_string(object.field("value_type"), &format!("{:?}", value_type));
+            write_expression(object.field("value"), value);
+            object.end();
+        }
+        Statement::Block(statements) => {
+            let mut object = Object::new(out, "Block");
+            write_list(object.field("statements"), statements, write_statement);
+            object.end();
+        }
+        Statement::If { cond, then_block, else_block } => {
+            let mut object = Object::new(out, "If");
+            write_expression(object.field("cond"), cond);
+            write_list(object.field("then_block"), then_block, write_statement);
+            write_option(object.field("else_block"), else_block.as_ref(), |out, block| write_list(out, block, write_statement));
+            object.end();
+        }
+        Statement::While { cond, body } => {
+            let mut object = Object::new(out, "While");
+            write_expression(object.field("cond"), cond);
+            write_list(object.field("body"), body, write_statement);
+            object.end();
+        }
+        Statement::DoWhile { body, cond } => {
+            let mut object = Object::new(out, "DoWhile");
+            write_list(object.field("body"), body, write_statement);
+            write_expression(object.field("cond"), cond);
+            object.end();
+        }
+        Statement::Repeat { count, body } => {
+            let mut object = Object::new(out, "Repeat");
+            write_expression(object.field("count"), count);
+            write_list(object.field("body"), body, write_statement);
+            object.end();
+        }
+        Statement::Match { subject, arms, default } => {
+            let mut object = Object::new(out, "Match");
+            write_expression(object.field("subject"), subject);
+            write_list(object.field("arms"), arms, |out, (pattern, body)| {
+                let mut arm = Object::new(out, "Arm");
+                write_expression(arm.field("pattern"), pattern);
+                write_list(arm.field("body"), body, write_statement);
+                arm.end();
+            });
+            write_option(object.field("default"), default.as_ref(), |out, block| write_list(out, block, write_statement));
+            object.end();
+        }
+        Statement::FunctionDef(function) => {
+            let mut object = Object::new(out, "FunctionDef");
+            write_string(object.field("name"), &function.name);
+            write_list(object.field("params"), &function.params, |out, param| {
+                let mut object = Object::new(out, "Param");
+                write_string(object.field("name"), &param.name);
+                write_option(object.field("default"), param.default.as_ref(), write_expression);
+                object.end();
+            });
+            write_list(object.field("body"), &function.body, write_statement);
+            object.end();
+        }
+        Statement::Return(value) => {
+            let mut object = Object::new(out, "Return");
+            write_expression(object.field("value"), value);
+            object.end();
+        }
+        Statement::Break => Object::new(out, "Break").end(),
+        Statement::Commented { comments, statement, trailing } => {
+            let mut object = Object::new(out, "Commented");
+            write_list(object.field("comments"), comments, |out, comment| write_string(out, comment));
+            write_statement(object.field("statement"), statement);
+            write_option(object.field("trailing"), trailing.as_ref(), |out, comment| write_string(out, comment));
+            object.end();
+        }
+        Statement::Comments(comments) => {
+            let mut object = Object::new(out, "Comments");
+            write_list(object.field("comments"), comments, |out, comment| write_string(out, comment));
+            object.end();
+        }
+        Statement::Continue => Object::new(out, "Continue").end(),
+    }
+}
+
+fn write_values(out: &mut String, kind: &str, values: &[Expression]) {
+    let mut object = Object::new(out, kind);
+    write_list(object.field("values"), values, write_expression);
+    object.end();
+}
+
+fn write_binding(out: &mut String, kind: &str, name: &str, value: &Expression) {
+    let mut object = Object::new(out, kind);
+    write_string(object.field("name"), name);
+    write_expression(object.field("value"), value);
+    object.end();
+}
+
+fn write_string_part(out: &mut String, part: &StringPart) {
+    match part {
+        StringPart::Text(text) => {
+            let mut object = Object::new(out, "Text");
+            write_string(object.field("text"), text);
+            object.end();
+        }
+        StringPart::Expression(expr) => {
+            let mut object = Object::new(out, "Expression");
+            write_expression(object.field("expr"), expr);
+            object.end();
+        }
+    }
+}
+
+fn write_color(out: &mut String, color: &Colored) {
+    match color {
+        Colored::Custom(red, green, blue) => {
+            let mut object = Object::new(out, "Custom");
+            object.field("red").push_str(&red.to_string());
+            object.field("green").push_str(&green.to_string());
+            object.field("blue").push_str(&blue.to_string());
+            object.end();
+        }
+        color => Object::new(out, &format!("{:?}", color)).end(),
+    }
+}
+
+fn write_expression(out: &mut String, expr: &Expression) {
+    match expr {
+        Expression::Number(n) => {
+            let mut object = Object::new(out, "Number");
+            write_number(object.field("value"), *n);
+            object.end();
+        }
+        Expression::Str(text) => {
+            let mut object = Object::new(out, "Str");
+            write_string(object.field("value"), text);
+            object.end();
+        }
+        Expression::Array(elements) => {
+            let mut object = Object::new(out, "Array");
+            write_list(object.field("elements"), elements, write_expression);
+            object.end();
+        }
+        Expression::Identifier(name) => {
+            let mut object = Object::new(out, "Identifier");
+            write_string(object.field("name"), name);
+            object.end();
+        }
+        Expression::Binary { op, left, right } => {
+            let mut object = Object::new(out, "Binary");
+            write_string(object.field("op"), &format!("{:?}", op));
+            write_expression(object.field("left"), left);
+            write_expression(object.field("right"), right);
+            object.end();
+        }
+        Expression::Unary { op, expr } => {
+            let mut object = Object::new(out, "Unary");
+            write_string(object.field("op"), &format!("{:?}", op));
+            write_expression(object.field("expr"), expr);
+            object.end();
+        }
+        Expression::Index { target, index } => {
+            let mut object = Object::new(out, "Index");
+            write_expression(object.field("target"), target);
+            write_expression(object.field("index"), index);
+            object.end();
+        }
+        Expression::Conditional { cond, then, otherwise } => {
+            let mut object = Object::new(out, "Conditional");
+            write_expression(object.field("cond"), cond);
+            write_expression(object.field("then"), then);
+            write_expression(object.field("otherwise"), otherwise);
+            object.end();
+        }
+        Expression::Logical { op, left, right } => {
+            let mut object = Object::new(out, "Logical");
+            write_string(object.field("op"), &format!("{:?}", op));
+            write_expression(object.field("left"), left);
+            write_expression(object.field("right"), right);
+            object.end();
+        }
+        Expression::Interpolation(parts) => {
+            let mut object = Object::new(out, "Interpolation");
+            write_list(object.field("parts"), parts, write_string_part);
+            object.end();
+        }
+        Expression::Clock => Object::new(out, "Clock").end(),
+        Expression::Random(range) => {
+            let mut object = Object::new(out, "Random");
+            write_option(object.field("range"), range.as_deref(), |out, range| write_list(out, range, write_expression));
+            object.end();
+        }
+        Expression::Call { name, args } => {
+            let mut object = Object::new(out, "Call");
+            write_string(object.field("name"), name);
+            write_list(object.field("args"), args, write_expression);
+            object.end();
+        }
+    }
+}
+
+fn write_list<T>(out: &mut String, items: &[T], mut write_item: impl FnMut(&mut String, &T)) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_item(out, item);
+    }
+    out.push(']');
+}
+
+fn write_option<T>(out: &mut String, value: Option<&T>, write_value: impl FnOnce(&mut String, &T)) {
+    match value {
+        Some(value) => write_value(out, value),
+        None => out.push_str("null"),
+    }
+}
+
+/// Writes the shortest decimal form that reads back as the same `f64`, or
+/// a string for infinities and NaN, which JSON has no number for.
+fn write_number(out: &mut String, n: f64) {
+    if n.is_finite() {
+        out.push_str(&format!("{:?}", n));
+    } else if n.is_nan() {
+        write_string(out, "NaN");
+    } else if n > 0.0 {
+        write_string(out, "inf");
+    } else {
+        write_string(out, "-inf");
+    }
+}
+
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn json(source: &str) -> String {
+        to_json(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn expressions_to_json() {
+        assert_eq!(json("zipette 0.1 + x * -2, t[0], [\"a\"], f(), hasard, hasard(1, 2), horloge, a ? b : c && d || ~e, 1 < 2;"), concat!(
+            r#"[{"type":"Print","values":["#,
+            r#"{"type":"Binary","op":"Sum","left":{"type":"Number","value":0.1},"#,
+            r#""right":{"type":"Binary","op":"Product","left":{"type":"Identifier","name":"x"},"#,
+            r#""right":{"type":"Unary","op":"Neg","expr":{"type":"Number","value":2.0}}}},"#,
+            r#"{"type":"Index","target":{"type":"Identifier","name":"t"},"index":{"type":"Number","value":0.0}},"#,
+            r#"{"type":"Array","elements":[{"type":"Str","value":"a"}]},"#,
+            r#"{"type":"Call","name":"f","args":[]},"#,
+            r#"{"type":"Random","range":null},"#,
+            r#"{"type":"Random","range":[{"type":"Number","value":1.0},{"type":"Number","value":2.0}]},"#,
+            r#"{"type":"Clock"},"#,
+            r#"{"type":"Conditional","cond":{"type":"Identifier","name":"a"},"then":{"type":"Identifier","name":"b"},"#,
+            r#""otherwise":{"type":"Logical","op":"Or","left":{"type":"Logical","op":"And","left":{"type":"Identifier","name":"c"},"#,
+            r#""right":{"type":"Identifier","name":"d"}},"right":{"type":"Unary","op":"BitNot","expr":{"type":"Identifier","name":"e"}}}},"#,
+            r#"{"type":"Binary","op":"Lt","left":{"type":"Number","value":1.0},"right":{"type":"Number","value":2.0}}"#,
+            r#"]}]"#,
+        ));
+    }
+
+    #[test]
+    fn statements_to_json() {
+        let source = "
+            ecrit 1; zipette; zipette \"n = ${n}!\"; demande n \"?\"; demande m;
+            lsd rouge 1; lsd (2) 1; lsd rgb(1, 2, 3) 1;
+            vicer x 1; x *= 2; x++; --x; fixe y 2; oublie x; importe \"a.zipette\"; vicer z: nombre 3; verifie x; quitte; quitte 1; attend 5;
+            { f(); }; si x { casse_pas(); } sinon { }; si x { };
+            tantque x { casse; }; fais { casse; } tantque x; boucle 2 { continue; };
+            fonction f(a, b) { renvoie a; }; fonction g(a, b = a + 1) { };
+            selon x { 1: { zipette 1; } 2: { } autre: { zipette; } };
+        ";
+        let expected = concat!(
+            r#"[{"type":"Write","values":[{"type":"Number","value":1.0}]},"#,
+            r#"{"type":"Print","values":[]},"#,
+            r#"{"type":"Print","values":[{"type":"Interpolation","parts":[{"type":"Text","text":"n = "},"#,
+            r#"{"type":"Expression","expr":{"type":"Identifier","name":"n"}},{"type":"Text","text":"!"}]}]},"#,
+            r#"{"type":"Input","name":"n","prompt":{"type":"Str","value":"?"}},"#,
+            r#"{"type":"Input","name":"m","prompt":null},"#,
+            r#"{"type":"PrintColored","color":{"type":"Red"},"name":"rouge","values":[{"type":"Number","value":1.0}]},"#,
+            r#"{"type":"PrintColoredDyn","color":{"type":"Number","value":2.0},"values":[{"type":"Number","value":1.0}]},"#,
+            r#"{"type":"PrintColoredRgb","components":[{"type":"Number","value":1.0},{"type":"Number","value":2.0},"#,
+            r#"{"type":"Number","value":3.0}],"values":[{"type":"Number","value":1.0}]},"#,
+            r#"{"type":"Assignment","name":"x","value":{"type":"Number","value":1.0}},"#,
+            r#"{"type":"CompoundAssignment","name":"x","op":"Product","value":{"type":"Number","value":2.0}},"#,
+            r#"{"type":"Step","name":"x","op":"Sum","prefix":false},"#,
+            r#"{"type":"Step","name":"x","op":"Minus","prefix":true},"#,
+            r#"{"type":"Constant","name":"y","value":{"type":"Number","value":2.0}},"#,
+            r#"{"type":"Forget","name":"x"},"#,
+            r#"{"type":"Import","file":"a.zipette"},"#,
+            r#"{"type":"TypedAssignment","name":"z","value_type":"Number","value":{"type":"Number","value":3.0}},"#,
+            r#"{"type":"Assert","cond":{"type":"Identifier","name":"x"}},"#,
+            r#"{"type":"Exit","code":null},"#,
+            r#"{"type":"Exit","code":{"type":"Number","value":1.0}},"#,
+            r#"{"type":"Sleep","duration":{"type":"Number","value":5.0}},"#,
+            r#"{"type":"Block","statements":[{"type":"Expression","expr":{"type":"Call","name":"f","args":[]}}]},"#,
+            r#"{"type":"If","cond":{"type":"Identifier","name":"x"},"#,
+            r#""then_block":[{"type":"Expression","expr":{"type":"Call","name":"casse_pas","args":[]}}],"else_block":[]},"#,
+            r#"{"type":"If","cond":{"type":"Identifier","name":"x"},"then_block":[],"else_block":null},"#,
+            r#"{"type":"While","cond":{"type":"Identifier","name":"x"},"body":[{"type":"Break"}]},"#,
+            r#"{"type":"DoWhile","body":[{"type":"Break"}],"cond":{"type":"Identifier","name":"x"}},"#,
+            r#"{"type":"Repeat","count":{"type":"Number","value":2.0},"body":[{"type":"Continue"}]},"#,
+            r#"{"type":"FunctionDef","name":"f","params":[{"type":"Param","name":"a","default":null},"#,
+            r#"{"type":"Param","name":"b","default":null}],"#,
+            r#""body":[{"type":"Return","value":{"type":"Identifier","name":"a"}}]},"#,
+            r#"{"type":"FunctionDef","name":"g","params":[{"type":"Param","name":"a","default":null},"#,
+            r#"{"type":"Param","name":"b","default":{"type":"Binary","op":"Sum","left":{"type":"Identifier","name":"a"},"#,
+            r#""right":{"type":"Number","value":1.0}}}],"body":[]},"#,
+            r#"{"type":"Match","subject":{"type":"Identifier","name":"x"},"arms":["#,
+            r#"{"type":"Arm","pattern":{"type":"Number","value":1.0},"body":[{"type":"Print","values":[{"type":"Number","value":1.0}]}]},"#,
+            r#"{"type":"Arm","pattern":{"type":"Number","value":2.0},"body":[]}],"default":[{"type":"Print","values":[]}]}]"#,
+        );
+        assert_eq!(json(source), expected);
+    }
+
+    #[test]
+    fn comments_to_json() {
+        let program = Parser::new(Lexer::new("# a\n# b\nzipette 1; # c\n{ zipette 2;\n# d\n};\n# e").with_comments()).parse().unwrap();
+        assert_eq!(to_json(&program), concat!(
+            r##"[{"type":"Commented","comments":["# a","# b"],"##,
+            r##""statement":{"type":"Print","values":[{"type":"Number","value":1.0}]},"trailing":"# c"},"##,
+            r##"{"type":"Block","statements":[{"type":"Print","values":[{"type":"Number","value":2.0}]},"##,
+            r##"{"type":"Comments","comments":["# d"]}]},"##,
+            r##"{"type":"Comments","comments":["# e"]}]"##,
+        ));
+    }
+
+    #[test]
+    fn numbers_and_strings_are_exact() {
+        let mut out = String::new();
+        for n in [0.1, 1e300, 1.0 / 3.0, 5e-324] {
+            out.clear();
+            write_number(&mut out, n);
+            assert_eq!(out.parse::<f64>().unwrap(), n);
+        }
+        assert_eq!(json("zipette 1e400;"), r#"[{"type":"Print","values":[{"type":"Number","value":"inf"}]}]"#);
+        let mut program = Parser::new(Lexer::new("zipette 1 / 0, -1 / 0, 0 / 0;")).parse().unwrap();
+        crate::fold::fold_constants(&mut program);
+        assert_eq!(to_json(&program), concat!(
+            r#"[{"type":"Print","values":[{"type":"Number","value":"inf"},"#,
+            r#"{"type":"Number","value":"-inf"},{"type":"Number","value":"NaN"}]}]"#,
+        ));
+        let program = [Statement::Print(vec![Expression::Str("a \"b\" \\ c\n\t\u{1}é".to_string())])];
+        assert_eq!(to_json(&program), r#"[{"type":"Print","values":[{"type":"Str","value":"a \"b\" \\ c\n\t\u0001é"}]}]"#);
+    }
+
+    #[test]
+    fn custom_colors_list_their_components() {
+        let mut out = String::new();
+        write_color(&mut out, &Colored::Custom(255, 0, 12));
+        assert_eq!(out, r#"{"type":"Custom","red":255,"green":0,"blue":12}"#);
+    }
+}
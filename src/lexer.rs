@@ -1,15 +1,23 @@
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::io::BufRead;
 
-#[derive(Debug)]
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Number(f64),
     EndOfStatement,
     Identifier(String),
+    Str(String),
+    /// A string literal containing at least one `${expression}`.
+    InterpolatedStr(Vec<StrPart>),
     Minus,
     Plus,
     Product,
     Division,
+    Modulo,
     Exponent,
     OpenParen,
     CloseParen,
@@ -17,63 +25,263 @@ pub enum Token {
     Bad(LexerError),
     BytesLeft,
     BytesRight,
+    Equal,
+    NotEqual,
+    LessEqual,
+    GreaterEqual,
+    Less,
+    Greater,
+    And,
+    Or,
+    Not,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    PlusAssign,
+    Increment,
+    Decrement,
+    MinusAssign,
+    StarAssign,
+    SlashAssign,
+    Comma,
+    Colon,
+    Question,
+    Arrow,
+    Assign,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    KwPrint,
+    KwPrintColored,
+    KwAssign,
+    KwTrue,
+    KwFalse,
+    KwIf,
+    KwElse,
+    KwWhile,
+    KwRepeat,
+    KwFunction,
+    KwReturn,
+    KwBreak,
+    KwContinue,
+    KwWrite,
+    KwInput,
+    KwConst,
+    KwAssert,
+    KwExit,
+    KwSleep,
+    KwMatch,
+    KwDefault,
+    KwDo,
+    KwForget,
+    KwImport,
+    KwRandom,
+    KwClock,
+    /// A comment, only produced by `Lexer::with_comments`: its source text
+    /// and whether it is alone on its line rather than after a token.
+    Comment { text: String, own_line: bool },
+    /// Stands in for a token that failed to lex in `Lexer::scan_all`.
+    Invalid,
+    Eof,
+}
+
+/// A piece of an interpolated string: literal text, or the source of an
+/// embedded expression left for the parser to lex again.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrPart {
+    Text(String),
+    Expr(String),
 }
 
+/// Position of a token in the source. Lines and columns start at 1 and
+/// `end_column` points just past the last character of the token.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Location {
-    line: usize,
-    start_column: usize,
-    end_column: usize
+    pub line: usize,
+    pub start_column: usize,
+    pub end_column: usize
 }
 
+/// Byte offsets of a token in the source, `end` being exclusive, so that
+/// `&source[span.start..span.end]` is the token's text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct LocalizedToken {
     pub token: Token,
-    pub loc: Location
+    pub loc: Location,
+    pub span: Span
 }
 
-#[derive(Debug)]
-pub struct LexerError(String);
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexerError {
+    pub message: String,
+    /// Span of the offending source, filled in once the token is complete.
+    pub loc: Option<Location>,
+}
+
+impl LexerError {
+    fn new(message: String) -> Self {
+        Self { message, loc: None }
+    }
+}
 
 impl Display for LexerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "LexerError: {}", self.0)
+        write!(f, "LexerError: {}", self.message)
     }
 }
 
 impl Error for LexerError {}
 
+#[derive(Clone)]
 pub struct Lexer<'a> {
     input: &'a str,
     cursor: usize,
+    line: usize,
+    column: usize,
+    tab_width: usize,
+    reached_eof: bool,
+    keep_comments: bool,
 }
 
 impl<'a> Lexer<'a> {
+    /// Creates a lexer over `input`, skipping a leading UTF-8 byte order mark
+    /// and a `#!` shebang line (which still counts as line 1).
     pub fn new(input: &'a str) -> Self {
-        Self { input, cursor: 0 }
+        let cursor = if input.starts_with(BYTE_ORDER_MARK) { BYTE_ORDER_MARK.len_utf8() } else { 0 };
+        let mut lexer = Self { input, cursor, line: 1, column: 1, tab_width: 1, reached_eof: false, keep_comments: false };
+        if input[cursor..].starts_with("#!") {
+            lexer.consume_while(|c| c != '\n');
+        }
+        lexer
+    }
+
+    /// Makes a tab advance the column to the next multiple of `width` (plus
+    /// one), so that reported columns match what a terminal shows. By default
+    /// a tab counts as a single column.
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        self.tab_width = width.max(1);
+        self
+    }
+
+    /// Produces comments as `Token::Comment` instead of skipping them, for
+    /// the parser to keep them in the tree.
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    /// Creates a lexer over a slice that continues a source at `line`/`column`.
+    pub(crate) fn resume(input: &'a str, line: usize, column: usize) -> Self {
+        Self { input, cursor: 0, line, column, tab_width: 1, reached_eof: false, keep_comments: false }
+    }
+
+    pub fn next_localized_token(&mut self) -> Option<LocalizedToken> {
+        let (line, start_column, start, mut token) = loop {
+            self.skip_whitespace();
+            let (line, start_column, start) = (self.line, self.column, self.cursor);
+            match self.skip_comment() {
+                Ok(true) if self.keep_comments => {
+                    let text = self.input[start..self.cursor].trim_end().to_string();
+                    let own_line = self.input[..start].rsplit('\n').next().is_none_or(|before| before.trim().is_empty());
+                    break (line, start_column, start, Token::Comment { text, own_line });
+                }
+                Ok(true) => continue,
+                Ok(false) => match self.scan_token() {
+                    Some(token) => break (line, start_column, start, token),
+                    None if !self.reached_eof => {
+                        self.reached_eof = true;
+                        break (line, start_column, start, Token::Eof);
+                    }
+                    None => return None,
+                },
+                Err(err) => break (line, start_column, start, Token::Bad(err)),
+            }
+        };
+        let loc = Location { line, start_column, end_column: self.column };
+        if let Token::Bad(err) = &mut token {
+            err.loc.get_or_insert_with(|| loc.clone());
+        }
+        Some(LocalizedToken { token, loc, span: Span { start, end: self.cursor } })
+    }
+
+    /// Tokenizes the whole input, replacing every token that failed to lex
+    /// with `Token::Invalid` and collecting the errors with their location,
+    /// so that they can all be reported at once.
+    pub fn scan_all(input: &str) -> (Vec<Token>, Vec<LexerError>) {
+        let mut lexer = Lexer::new(input);
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(LocalizedToken { token, loc, .. }) = lexer.next_localized_token() {
+            match token.into_result() {
+                Ok(token) => tokens.push(token),
+                Err(mut err) => {
+                    err.loc.get_or_insert(loc);
+                    errors.push(err);
+                    tokens.push(Token::Invalid);
+                }
+            }
+        }
+        (tokens, errors)
+    }
+
+    /// Creates a lexer pulling its input from `reader` line by line instead of
+    /// borrowing the whole source.
+    pub fn from_reader<R: BufRead>(reader: R) -> StreamingLexer<R> {
+        StreamingLexer::new(reader)
+    }
+
+    pub fn localized(mut self) -> impl Iterator<Item = LocalizedToken> + 'a {
+        std::iter::from_fn(move || self.next_localized_token())
     }
 
     pub fn next_token(&mut self) -> Option<Token> {
-        self.skip_whitespace();
+        self.next_localized_token().map(|localized| localized.token)
+    }
+
+    fn scan_token(&mut self) -> Option<Token> {
         let c = self.peek_char()?;
 
         let token = match c {
             '-' => {
                 self.consume();
-                if let Some(next) = self.peek_char() {
-                    if next.is_ascii_digit() {
-                        return Some(self.parse_number(true));
-                    }
+                if self.consume_if('>') {
+                    return Some(Token::Arrow);
+                }
+                if self.consume_if('=') {
+                    return Some(Token::MinusAssign);
+                }
+                // `-->` stays `-` followed by `->`.
+                if self.peek_char() == Some('-') && self.peek_second_char() != Some('>') {
+                    self.consume();
+                    return Some(Token::Decrement);
                 }
                 Token::Minus
             }
             '+' => {
                 self.consume();
-                Token::Plus
+                if self.consume_if('=') {
+                    Token::PlusAssign
+                } else if self.consume_if('+') {
+                    Token::Increment
+                } else {
+                    Token::Plus
+                }
             }
             '*' => {
                 self.consume();
                 if self.peek_char() == Some('*') {
                     self.consume();
                     Token::Exponent
+                } else if self.consume_if('=') {
+                    Token::StarAssign
                 } else {
                     Token::Product
                 }
@@ -96,34 +304,103 @@ impl<'a> Lexer<'a> {
             },
             '/' => {
                 self.consume();
-                Token::Division
+                if self.consume_if('=') {
+                    Token::SlashAssign
+                } else {
+                    Token::Division
+                }
+            },
+            '%' => {
+                self.consume();
+                Token::Modulo
+            },
+            ',' => {
+                self.consume();
+                Token::Comma
+            },
+            ':' => {
+                self.consume();
+                Token::Colon
+            },
+            '?' => {
+                self.consume();
+                Token::Question
+            },
+            '{' => {
+                self.consume();
+                Token::OpenBrace
+            },
+            '}' => {
+                self.consume();
+                Token::CloseBrace
+            },
+            '[' => {
+                self.consume();
+                Token::OpenBracket
+            },
+            ']' => {
+                self.consume();
+                Token::CloseBracket
             },
             '<' => {
-                if let Some(ch) = self.peek_char() {
-                    if ch == '<' {
-                        Token::BytesLeft
-                    } else {
-                        Token::Useless(ch)
-                    }
+                self.consume();
+                if self.consume_if('<') {
+                    Token::BytesLeft
+                } else if self.consume_if('=') {
+                    Token::LessEqual
                 } else {
-                    Token::Useless('<')
+                    Token::Less
                 }
             },
             '>' => {
                 self.consume();
-                if let Some(ch) = self.peek_char() {
-                    if ch == '>' {
-                        self.consume();
-                        Token::BytesRight
-                    } else {
-                        Token::Useless('>')
-                    }
+                if self.consume_if('>') {
+                    Token::BytesRight
+                } else if self.consume_if('=') {
+                    Token::GreaterEqual
+                } else {
+                    Token::Greater
+                }
+            },
+            '=' => {
+                self.consume();
+                if self.consume_if('=') {
+                    Token::Equal
+                } else {
+                    Token::Assign
+                }
+            },
+            '!' => {
+                self.consume();
+                if self.consume_if('=') {
+                    Token::NotEqual
+                } else {
+                    Token::Not
+                }
+            },
+            '&' => {
+                self.consume();
+                if self.consume_if('&') {
+                    Token::And
                 } else {
-                    Token::Useless('>')
+                    Token::BitAnd
                 }
             },
-            '0'..='9' => self.parse_number(false),
-            'a'..='z' | 'A'..='Z' => self.parse_identifier(),
+            '|' => {
+                self.consume();
+                if self.consume_if('|') {
+                    Token::Or
+                } else {
+                    Token::BitOr
+                }
+            },
+            '~' => {
+                self.consume();
+                Token::BitNot
+            },
+            '"' => self.parse_string(),
+            '0'..='9' => self.parse_number(),
+            'a'..='z' | 'A'..='Z' | '_' => self.parse_identifier(),
             _ => {
                 self.consume();
                 Token::Useless(c)
@@ -133,43 +410,245 @@ impl<'a> Lexer<'a> {
         Some(token)
     }
 
-    fn parse_number(&mut self, is_negative: bool) -> Token {
-        let mut num_str = self.consume_while(|c| c.is_ascii_digit());
+    fn parse_number(&mut self) -> Token {
+        if self.peek_char() == Some('0') {
+            let radix = match self.peek_second_char() {
+                Some('x' | 'X') => Some(16),
+                Some('b' | 'B') => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                return self.parse_radix_number(radix);
+            }
+        }
+
+        let start = self.cursor;
+        self.consume_ascii_while(is_digit_or_separator);
 
-        if self.peek_char() == Some('.') || self.peek_char() == Some(',') {
+        if self.at_decimal_separator() {
             self.consume();
-            num_str += ".";
-            num_str += self.consume_while(|r#c| c.is_ascii_digit()).as_str();
+            if self.consume_ascii_while(is_digit_or_separator).is_empty() {
+                return Token::Bad(LexerError::new(format!("Missing digits after the decimal point in number literal '{}'", &self.input[start..self.cursor])));
+            }
+
+            if self.at_decimal_separator() {
+                while self.at_decimal_separator() || self.peek_char().is_some_and(|c| c.is_ascii() && is_digit_or_separator(c as u8)) {
+                    self.consume();
+                }
+                return Token::Bad(LexerError::new(format!("Invalid number literal '{}'", &self.input[start..self.cursor])));
+            }
+        }
+
+        if let Some('e' | 'E') = self.peek_char() {
+            self.consume();
+            if let Some('+' | '-') = self.peek_char() {
+                self.consume();
+            }
+            if self.consume_ascii_while(is_digit_or_separator).is_empty() {
+                return Token::Bad(LexerError::new(format!("Missing exponent digits in number literal '{}'", &self.input[start..self.cursor])));
+            }
+        }
+
+        let literal = &self.input[start..self.cursor];
+        if has_misplaced_separator(literal, 10) {
+            return Token::Bad(LexerError::new(format!("Invalid underscore placement in number literal '{}'", literal)));
+        }
+
+        // Only literals using `_` or a decimal comma need a normalized copy.
+        let parsed = if literal.contains(['_', ',']) {
+            literal.replace('_', "").replace(',', ".").parse::<f64>()
+        } else {
+            literal.parse::<f64>()
+        };
+        match parsed {
+            Ok(n) => Token::Number(n),
+            Err(_) => Token::Bad(LexerError::new(format!("Invalid number: {}", literal))),
         }
+    }
 
-        match num_str.parse::<f64>() {
-            Ok(n) => Token::Number(if is_negative { -n } else { n }),
-            Err(_) => Token::Bad(LexerError(format!("Invalid number: {}", num_str))),
+    /// A comma is only a decimal separator when a digit follows it right
+    /// away, so that `f(1, 2)` or `1,x` keep their comma.
+    fn at_decimal_separator(&self) -> bool {
+        match self.peek_char() {
+            Some('.') => true,
+            Some(',') => self.peek_second_char().is_some_and(|c| c.is_ascii_digit()),
+            _ => false,
+        }
+    }
+
+    /// Parses a `0x` or `0b` literal. The whole alphanumeric run is consumed
+    /// so that a literal like `0b102` is reported as one bad token.
+    fn parse_radix_number(&mut self, radix: u32) -> Token {
+        let start = self.cursor;
+        self.consume();
+        self.consume();
+        let digits = self.consume_ascii_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+        let literal = &self.input[start..self.cursor];
+
+        if digits.is_empty() {
+            return Token::Bad(LexerError::new(format!("Missing digits in number literal '{literal}'")));
+        }
+        if let Some(bad) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            return Token::Bad(LexerError::new(format!("Invalid digit '{bad}' in number literal '{literal}'")));
+        }
+        if has_misplaced_separator(digits, radix) {
+            return Token::Bad(LexerError::new(format!("Invalid underscore placement in number literal '{literal}'")));
+        }
+
+        let parsed = if digits.contains('_') {
+            u64::from_str_radix(&digits.replace('_', ""), radix)
+        } else {
+            u64::from_str_radix(digits, radix)
+        };
+        match parsed {
+            Ok(n) => Token::Number(n as f64),
+            Err(_) => Token::Bad(LexerError::new(format!("Number literal '{literal}' is too large"))),
         }
     }
 
+    fn parse_string(&mut self) -> Token {
+        let (line, column) = (self.line, self.column);
+        self.consume();
+        let mut parts = Vec::new();
+        let mut text_start = self.cursor;
+        loop {
+            match self.peek_char() {
+                None => return Token::Bad(LexerError::new(format!("Unterminated string opened at line {line}, column {column}"))),
+                Some('"') => break,
+                Some('$') if self.peek_second_char() == Some('{') => {
+                    let (open_line, open_column) = (self.line, self.column);
+                    if self.cursor > text_start {
+                        parts.push(StrPart::Text(self.input[text_start..self.cursor].to_string()));
+                    }
+                    self.consume();
+                    self.consume();
+                    let expression = self.consume_while(|c| c != '}' && c != '"');
+                    if !self.consume_if('}') {
+                        // Skip the rest of the string so lexing resumes after it.
+                        self.consume_while(|c| c != '"');
+                        self.consume();
+                        let mut err = LexerError::new(format!("Unclosed '${{' at line {open_line}, column {open_column}"));
+                        err.loc = Some(Location { line: open_line, start_column: open_column, end_column: open_column + 2 });
+                        return Token::Bad(err);
+                    }
+                    parts.push(StrPart::Expr(expression.to_string()));
+                    text_start = self.cursor;
+                }
+                Some(_) => {
+                    self.consume();
+                }
+            }
+        }
+        let tail = &self.input[text_start..self.cursor];
+        self.consume();
+        if parts.is_empty() {
+            return Token::Str(tail.to_string());
+        }
+        if !tail.is_empty() {
+            parts.push(StrPart::Text(tail.to_string()));
+        }
+        Token::InterpolatedStr(parts)
+    }
+
     fn skip_whitespace(&mut self) {
-        self.consume_while(|c| c.is_whitespace());
+        loop {
+            match self.input.as_bytes().get(self.cursor) {
+                Some(b' ') => {
+                    self.cursor += 1;
+                    self.column += 1;
+                }
+                Some(b) if b.is_ascii_whitespace() || *b == 0x0b => {
+                    self.consume();
+                }
+                Some(b) if !b.is_ascii() && self.peek_char().is_some_and(char::is_whitespace) => {
+                    self.consume();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Skips a `#` line comment or a `/* ... */` block comment if one starts
+    /// at the cursor, returning whether anything was skipped.
+    fn skip_comment(&mut self) -> Result<bool, LexerError> {
+        match (self.peek_char(), self.peek_second_char()) {
+            (Some('#'), _) => {
+                self.consume_while(|c| c != '\n');
+                Ok(true)
+            }
+            (Some('/'), Some('*')) => {
+                let (line, column) = (self.line, self.column);
+                self.consume();
+                self.consume();
+                loop {
+                    match self.consume() {
+                        Some('*') if self.peek_char() == Some('/') => {
+                            self.consume();
+                            return Ok(true);
+                        }
+                        Some('/') if self.peek_char() == Some('*') => {
+                            return Err(LexerError::new(format!("Nested block comments are not supported (comment opened at line {line}, column {column})")));
+                        }
+                        Some(_) => {}
+                        None => {
+                            return Err(LexerError::new(format!("Unterminated block comment opened at line {line}, column {column}")));
+                        }
+                    }
+                }
+            }
+            _ => Ok(false),
+        }
     }
 
     fn parse_identifier(&mut self) -> Token {
-        Token::Identifier(self.consume_while(|c| c.is_ascii_alphabetic() && c != ';'))
+        let identifier = self.consume_ascii_while(|b| b.is_ascii_alphanumeric() || b == b'_');
+        keyword(identifier).unwrap_or_else(|| Token::Identifier(identifier.to_string()))
     }
 
     fn consume(&mut self) -> Option<char> {
-        if let Some(c) = self.input[self.cursor..].chars().next() {
+        if let Some(c) = self.peek_char() {
             self.cursor += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else if c == '\r' && self.peek_char() == Some('\n') {
+                // `\r\n` is a single line break, the `\n` does the accounting.
+            } else if c == '\t' {
+                self.column += self.tab_width - (self.column - 1) % self.tab_width;
+            } else {
+                self.column += 1;
+            }
             Some(c)
         } else {
             None
         }
     }
 
+    fn consume_if(&mut self, expected: char) -> bool {
+        if self.peek_char() == Some(expected) {
+            self.consume();
+            true
+        } else {
+            false
+        }
+    }
+
     fn peek_char(&self) -> Option<char> {
-        self.input[self.cursor..].chars().next()
+        match self.input.as_bytes().get(self.cursor) {
+            // ASCII needs no UTF-8 decoding.
+            Some(&b) if b.is_ascii() => Some(b as char),
+            Some(_) => self.input[self.cursor..].chars().next(),
+            None => None,
+        }
     }
 
-    fn consume_while<F>(&mut self, condition: F) -> String
+    fn peek_second_char(&self) -> Option<char> {
+        self.input[self.cursor..].chars().nth(1)
+    }
+
+    /// Consumes characters while `condition` holds and returns them as a
+    /// slice of the input, without allocating.
+    fn consume_while<F>(&mut self, condition: F) -> &'a str
     where
         F: Fn(char) -> bool,
     {
@@ -180,14 +659,1121 @@ impl<'a> Lexer<'a> {
             }
             self.consume();
         }
-        self.input[start..self.cursor].to_string()
+        &self.input[start..self.cursor]
+    }
+
+    /// Byte-level `consume_while` for runs of ASCII characters that are
+    /// neither line breaks nor tabs, so each byte is exactly one column. The
+    /// scan stops at the first non-ASCII byte and thus never splits a character.
+    fn consume_ascii_while(&mut self, condition: fn(u8) -> bool) -> &'a str {
+        let start = self.cursor;
+        let length = self.input.as_bytes()[start..]
+            .iter()
+            .take_while(|&&b| b.is_ascii() && b != b'\n' && b != b'\t' && b != b'\r' && condition(b))
+            .count();
+        self.cursor += length;
+        self.column += length;
+        &self.input[start..self.cursor]
+    }
+}
+
+/// Returns the token for a reserved word, if `name` is one.
+pub fn keyword(name: &str) -> Option<Token> {
+    match name {
+        "zipette" => Some(Token::KwPrint),
+        "lsd" => Some(Token::KwPrintColored),
+        "vicer" => Some(Token::KwAssign),
+        "vrai" => Some(Token::KwTrue),
+        "faux" => Some(Token::KwFalse),
+        "si" => Some(Token::KwIf),
+        "sinon" => Some(Token::KwElse),
+        "tantque" => Some(Token::KwWhile),
+        "boucle" => Some(Token::KwRepeat),
+        "fonction" => Some(Token::KwFunction),
+        "renvoie" => Some(Token::KwReturn),
+        "casse" => Some(Token::KwBreak),
+        "continue" => Some(Token::KwContinue),
+        "ecrit" => Some(Token::KwWrite),
+        "demande" => Some(Token::KwInput),
+        "fixe" => Some(Token::KwConst),
+        "verifie" => Some(Token::KwAssert),
+        "quitte" => Some(Token::KwExit),
+        "attend" => Some(Token::KwSleep),
+        "selon" => Some(Token::KwMatch),
+        "autre" => Some(Token::KwDefault),
+        "fais" => Some(Token::KwDo),
+        "oublie" => Some(Token::KwForget),
+        "importe" => Some(Token::KwImport),
+        "hasard" => Some(Token::KwRandom),
+        "horloge" => Some(Token::KwClock),
+        // `^` is already the exponent operator, so exclusive or is spelled out.
+        "xor" => Some(Token::BitXor),
+        _ => None,
+    }
+}
+
+fn is_digit_or_separator(b: u8) -> bool {
+    b.is_ascii_digit() || b == b'_'
+}
+
+/// Underscores may only separate two digits: `1_000` is fine, while `_1`,
+/// `1_`, `1__0` and `1_.5` are not.
+fn has_misplaced_separator(literal: &str, radix: u32) -> bool {
+    let mut previous = None;
+    let mut chars = literal.chars().peekable();
+    while let Some(c) = chars.next() {
+        let between_digits = previous.is_some_and(|p: char| p.is_digit(radix)) && chars.peek().is_some_and(|next| next.is_digit(radix));
+        if c == '_' && !between_digits {
+            return true;
+        }
+        previous = Some(c);
+    }
+    false
+}
+
+impl Display for Token {
+    /// Renders the token the way a script author would recognize it.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Identifier(id) => write!(f, "identifier '{}'", id),
+            Token::Str(content) => write!(f, "string \"{}\"", content),
+            Token::InterpolatedStr(parts) => {
+                write!(f, "string \"")?;
+                for part in parts {
+                    match part {
+                        StrPart::Text(text) => write!(f, "{}", text)?,
+                        StrPart::Expr(source) => write!(f, "${{{}}}", source)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Token::Useless(c) => write!(f, "unexpected character '{}'", c),
+            Token::Bad(err) => write!(f, "invalid token ({})", err.message),
+            Token::Invalid => write!(f, "invalid token"),
+            Token::Comment { .. } => write!(f, "comment"),
+            Token::Eof => write!(f, "end of file"),
+            Token::KwPrint | Token::KwPrintColored | Token::KwAssign | Token::KwTrue | Token::KwFalse
+            | Token::KwIf | Token::KwElse | Token::KwWhile | Token::KwRepeat | Token::KwFunction
+            | Token::KwReturn | Token::KwBreak | Token::KwContinue | Token::KwWrite
+            | Token::KwInput | Token::KwConst | Token::KwAssert | Token::KwExit
+            | Token::KwSleep | Token::KwMatch | Token::KwDefault
+            | Token::KwDo | Token::KwForget | Token::KwImport | Token::KwRandom
+            | Token::KwClock => {
+                write!(f, "keyword '{}'", self.keyword_name().unwrap_or_default())
+            }
+            token => write!(f, "'{}'", token.symbol().unwrap_or_default()),
+        }
+    }
+}
+
+impl Token {
+    /// The source spelling of an operator or punctuation token.
+    fn symbol(&self) -> Option<&'static str> {
+        let symbol = match self {
+            Token::EndOfStatement => ";",
+            Token::Minus => "-",
+            Token::Plus => "+",
+            Token::Product => "*",
+            Token::Division => "/",
+            Token::Modulo => "%",
+            Token::Exponent => "**",
+            Token::OpenParen => "(",
+            Token::CloseParen => ")",
+            Token::BytesLeft => "<<",
+            Token::BytesRight => ">>",
+            Token::Equal => "==",
+            Token::NotEqual => "!=",
+            Token::LessEqual => "<=",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::Greater => ">",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Not => "!",
+            Token::BitAnd => "&",
+            Token::BitOr => "|",
+            Token::BitXor => "xor",
+            Token::BitNot => "~",
+            Token::PlusAssign => "+=",
+            Token::Increment => "++",
+            Token::Decrement => "--",
+            Token::MinusAssign => "-=",
+            Token::StarAssign => "*=",
+            Token::SlashAssign => "/=",
+            Token::Comma => ",",
+            Token::Colon => ":",
+            Token::Question => "?",
+            Token::Arrow => "->",
+            Token::Assign => "=",
+            Token::OpenBrace => "{",
+            Token::CloseBrace => "}",
+            Token::OpenBracket => "[",
+            Token::CloseBracket => "]",
+            _ => return None,
+        };
+        Some(symbol)
+    }
+
+    /// The token's text as it appears in a dump: the value of literals, the
+    /// message of a bad token and nothing for the end of file.
+    fn lexeme(&self) -> String {
+        match self {
+            Token::Number(n) => n.to_string(),
+            Token::Identifier(id) => id.clone(),
+            Token::Str(_) | Token::InterpolatedStr(_) => self.to_string()["string ".len()..].to_string(),
+            Token::Useless(c) => c.to_string(),
+            Token::Bad(err) => err.message.clone(),
+            Token::Comment { text, .. } => text.clone(),
+            token => token.keyword_name().or_else(|| token.symbol()).unwrap_or_default().to_string(),
+        }
+    }
+
+    /// The variant name, e.g. `Number` or `OpenParen`.
+    fn kind(&self) -> String {
+        let debug = format!("{:?}", self);
+        debug.split(['(', ' ']).next().unwrap_or_default().to_string()
+    }
+}
+
+/// Lexes the whole input into located tokens, problem tokens included, so
+/// that the dump is a faithful record of the scan.
+pub fn dump(source: &str) -> Vec<LocalizedToken> {
+    Lexer::new(source).localized().collect()
+}
+
+/// Renders a dump one token per line as `line:col  KIND  'lexeme'`.
+pub fn render(tokens: &[LocalizedToken]) -> String {
+    tokens.iter()
+        .map(|localized| {
+            let position = format!("{}:{}", localized.loc.line, localized.loc.start_column);
+            format!("{:<6}  {:<15}  '{}'\n", position, localized.token.kind(), localized.token.lexeme())
+        })
+        .collect()
+}
+
+/// Lexes the whole input once, keeping problem tokens (`Bad`, `Useless`) in
+/// the stream, so that callers can walk the same tokens several times.
+pub fn tokens(input: &str) -> Vec<Token> {
+    let mut lexer = Lexer::new(input);
+    std::iter::from_fn(|| lexer.next_token()).collect()
+}
+
+/// Reads `text` the way a number literal is read in source code (`3,5`,
+/// `1_000`, `0x1F`...), allowing a leading `-` and surrounding whitespace.
+pub fn number(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1.0, digits),
+        None => (1.0, text),
+    };
+    match tokens(digits).as_slice() {
+        [Token::Number(n), Token::Eof] => Some(sign * n),
+        _ => None,
+    }
+}
+
+impl Token {
+    /// The source spelling of keyword tokens.
+    pub fn keyword_name(&self) -> Option<&'static str> {
+        match self {
+            Token::KwPrint => Some("zipette"),
+            Token::KwPrintColored => Some("lsd"),
+            Token::KwAssign => Some("vicer"),
+            Token::KwTrue => Some("vrai"),
+            Token::KwFalse => Some("faux"),
+            Token::KwIf => Some("si"),
+            Token::KwElse => Some("sinon"),
+            Token::KwWhile => Some("tantque"),
+            Token::KwRepeat => Some("boucle"),
+            Token::KwFunction => Some("fonction"),
+            Token::KwReturn => Some("renvoie"),
+            Token::KwBreak => Some("casse"),
+            Token::KwContinue => Some("continue"),
+            Token::KwWrite => Some("ecrit"),
+            Token::KwInput => Some("demande"),
+            Token::KwConst => Some("fixe"),
+            Token::KwAssert => Some("verifie"),
+            Token::KwExit => Some("quitte"),
+            Token::KwSleep => Some("attend"),
+            Token::KwMatch => Some("selon"),
+            Token::KwDefault => Some("autre"),
+            Token::KwDo => Some("fais"),
+            Token::KwForget => Some("oublie"),
+            Token::KwImport => Some("importe"),
+            Token::KwRandom => Some("hasard"),
+            Token::KwClock => Some("horloge"),
+            _ => None,
+        }
+    }
+
+    /// Turns the tokens that only record a lexing problem into errors.
+    pub fn into_result(self) -> Result<Token, LexerError> {
+        match self {
+            Token::Bad(err) => Err(err),
+            Token::Useless(c) => Err(LexerError::new(format!("Unexpected character '{}'", c))),
+            token => Ok(token),
+        }
+    }
+}
+
+/// Lexer over a `BufRead`, producing the same tokens as `Lexer` while only
+/// keeping the unlexed part of the current lines in memory.
+///
+/// Input is read one line at a time. A token is only handed out once the
+/// buffer extends past it, so constructs spanning several lines (block
+/// comments, strings) simply pull more lines until they are complete.
+pub struct StreamingLexer<R: BufRead> {
+    reader: R,
+    buffer: String,
+    /// Bytes already drained from `buffer`, so spans stay relative to the whole stream.
+    drained: usize,
+    offset: usize,
+    line: usize,
+    column: usize,
+    exhausted: bool,
+    reached_eof: bool,
+}
+
+impl<R: BufRead> StreamingLexer<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, buffer: String::new(), drained: 0, offset: 0, line: 1, column: 1, exhausted: false, reached_eof: false }
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        self.next_localized_token().map(|localized| localized.token)
+    }
+
+    pub fn next_localized_token(&mut self) -> Option<LocalizedToken> {
+        loop {
+            let mut lexer = if self.drained + self.offset == 0 {
+                Lexer::new(&self.buffer)
+            } else {
+                Lexer::resume(&self.buffer[self.offset..], self.line, self.column)
+            };
+            let mut localized = lexer.next_localized_token()?;
+
+            let complete = !matches!(localized.token, Token::Eof) && self.offset + lexer.cursor < self.buffer.len();
+            if complete || self.exhausted {
+                if matches!(localized.token, Token::Eof) {
+                    if self.reached_eof {
+                        return None;
+                    }
+                    self.reached_eof = true;
+                }
+                let base = self.drained + self.offset;
+                localized.span = Span { start: base + localized.span.start, end: base + localized.span.end };
+                self.offset += lexer.cursor;
+                self.line = lexer.line;
+                self.column = lexer.column;
+                return Some(localized);
+            }
+
+            self.buffer.drain(..self.offset);
+            self.drained += self.offset;
+            self.offset = 0;
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => self.exhausted = true,
+                Ok(_) => {}
+                Err(err) => {
+                    self.exhausted = true;
+                    let loc = Location { line: self.line, start_column: self.column, end_column: self.column };
+                    let err = LexerError { message: format!("Failed to read input: {}", err), loc: Some(loc.clone()) };
+                    let at = self.drained + self.buffer.len();
+                    return Some(LocalizedToken { token: Token::Bad(err), loc, span: Span { start: at, end: at } });
+                }
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for StreamingLexer<R> {
+    type Item = Result<Token, LexerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().map(Token::into_result)
+    }
+}
+
+/// Wraps any token iterator so that upcoming items can be inspected without
+/// consuming them. Peeked items are kept in a small buffer and handed out by
+/// `next` in order.
+pub struct Lookahead<I: Iterator> {
+    inner: I,
+    buffer: VecDeque<I::Item>,
+}
+
+impl<I: Iterator> Lookahead<I> {
+    pub fn new(inner: I) -> Self {
+        Self { inner, buffer: VecDeque::new() }
+    }
+
+    pub fn peek(&mut self) -> Option<&I::Item> {
+        self.peek_nth(0)
+    }
+
+    /// Returns the item `n` positions ahead, `peek_nth(0)` being the next one.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&I::Item> {
+        while self.buffer.len() <= n {
+            self.buffer.push_back(self.inner.next()?);
+        }
+        self.buffer.get(n)
+    }
+}
+
+impl<I: Iterator> Iterator for Lookahead<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.buffer.pop_front().or_else(|| self.inner.next())
+    }
+}
+
+/// Item the parser reads tokens from: either a token alone, or a
+/// `LocalizedToken` whose position ends up in parse errors.
+pub trait ParserInput {
+    fn token(&self) -> Option<&Token>;
+    /// The token, or the error it stands for, with where it was read if
+    /// that is known.
+    fn into_token(self) -> (Result<Token, LexerError>, Option<Location>);
+}
+
+impl ParserInput for Result<Token, LexerError> {
+    fn token(&self) -> Option<&Token> {
+        self.as_ref().ok()
+    }
+
+    fn into_token(self) -> (Result<Token, LexerError>, Option<Location>) {
+        (self, None)
+    }
+}
+
+impl ParserInput for LocalizedToken {
+    fn token(&self) -> Option<&Token> {
+        Some(&self.token)
+    }
+
+    fn into_token(self) -> (Result<Token, LexerError>, Option<Location>) {
+        (self.token.into_result(), Some(self.loc))
     }
 }
 
 impl<> Iterator for Lexer<'_> {
-    type Item = Token;
+    type Item = Result<Token, LexerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.next_token()
+        self.next_token().map(Token::into_result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lexes `source` up to, but not including, the final `Eof`.
+    fn lex(source: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(source);
+        std::iter::from_fn(|| lexer.next_token())
+            .take_while(|token| !matches!(token, Token::Eof))
+            .collect()
+    }
+
+    #[test]
+    fn shift_left_is_a_single_token() {
+        let tokens = lex("1 << 2;");
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1.0));
+        assert!(matches!(tokens[1], Token::BytesLeft));
+        assert!(matches!(tokens[2], Token::Number(n) if n == 2.0));
+        assert!(matches!(tokens[3], Token::EndOfStatement));
+    }
+
+    #[test]
+    fn lone_less_than_is_consumed() {
+        let tokens = lex("1 < 2;");
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1.0));
+        assert!(matches!(tokens[1], Token::Less));
+        assert!(matches!(tokens[2], Token::Number(n) if n == 2.0));
+        assert!(matches!(tokens[3], Token::EndOfStatement));
+    }
+
+    #[test]
+    fn trailing_less_than_terminates() {
+        let tokens = lex("1 <");
+        assert_eq!(tokens.len(), 2);
+        assert!(matches!(tokens[1], Token::Less));
+    }
+
+    #[test]
+    fn localized_tokens_track_lines_and_columns() {
+        let tokens: Vec<LocalizedToken> = Lexer::new("zipette 12;\n  vicer x 3;").localized().collect();
+        let locs: Vec<(usize, usize, usize)> = tokens.iter()
+            .map(|t| (t.loc.line, t.loc.start_column, t.loc.end_column))
+            .collect();
+        assert_eq!(locs, vec![
+            (1, 1, 8), (1, 9, 11), (1, 11, 12),
+            (2, 3, 8), (2, 9, 10), (2, 11, 12), (2, 12, 13), (2, 13, 13),
+        ]);
+    }
+
+    #[test]
+    fn multi_byte_characters_advance_column_by_one() {
+        let tokens: Vec<LocalizedToken> = Lexer::new("é 1").localized().collect();
+        assert!(matches!(tokens[0].token, Token::Useless('é')));
+        assert_eq!((tokens[0].loc.start_column, tokens[0].loc.end_column), (1, 2));
+        assert_eq!((tokens[1].loc.start_column, tokens[1].loc.end_column), (3, 4));
+    }
+
+    #[test]
+    fn comment_before_statement_is_skipped() {
+        let tokens = lex("# set up x\nvicer x 3;");
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[0], Token::KwAssign));
+        assert!(matches!(tokens[3], Token::EndOfStatement));
+    }
+
+    #[test]
+    fn comment_after_semicolon_is_skipped() {
+        let tokens = lex("vicer x 3; # set up x\nzipette x; # no newline");
+        assert_eq!(tokens.len(), 7);
+        assert!(matches!(tokens[4], Token::KwPrint));
+        assert!(matches!(tokens[6], Token::EndOfStatement));
+    }
+
+    #[test]
+    fn only_comments_yield_no_tokens() {
+        assert!(lex("# one\n   # two\n#three").is_empty());
+    }
+
+    #[test]
+    fn comments_kept_on_request() {
+        let mut lexer = Lexer::new("# x\nvicer x 3; # trois\r\n  /* a\n b */ zipette x;").with_comments();
+        let tokens: Vec<Token> = std::iter::from_fn(|| lexer.next_token()).collect();
+        let comment = |text: &str, own_line| Token::Comment { text: text.to_string(), own_line };
+        assert_eq!(tokens, [
+            comment("# x", true), Token::KwAssign, Token::Identifier("x".to_string()), Token::Number(3.0), Token::EndOfStatement,
+            comment("# trois", false), comment("/* a\n b */", true), Token::KwPrint, Token::Identifier("x".to_string()),
+            Token::EndOfStatement, Token::Eof,
+        ]);
+    }
+
+    #[test]
+    fn block_comment_inside_expression_is_skipped() {
+        let tokens = lex("zipette 1 + /* ignored\n over lines */ 2 / 4;");
+        assert_eq!(tokens.len(), 7);
+        assert!(matches!(tokens[2], Token::Plus));
+        assert!(matches!(tokens[3], Token::Number(n) if n == 2.0));
+        assert!(matches!(tokens[4], Token::Division));
+    }
+
+    #[test]
+    fn unterminated_block_comment_is_reported_where_it_starts() {
+        let tokens: Vec<LocalizedToken> = Lexer::new("zipette 1;\n  /* never closed\nzipette 2;").localized().collect();
+        assert_eq!(tokens.len(), 5);
+        assert!(matches!(&tokens[3].token, Token::Bad(err) if err.message.contains("line 2, column 3")));
+        assert!(matches!(tokens[4].token, Token::Eof));
+        assert_eq!((tokens[3].loc.line, tokens[3].loc.start_column), (2, 3));
+    }
+
+    #[test]
+    fn nested_block_comment_is_rejected() {
+        let tokens = lex("/* outer /* inner */ */");
+        assert!(matches!(&tokens[0], Token::Bad(err) if err.message.contains("Nested")));
+    }
+
+    #[test]
+    fn string_keeps_operators_and_semicolons() {
+        let tokens = lex("zipette \"a + b;\";");
+        assert_eq!(tokens.len(), 3);
+        assert!(matches!(&tokens[1], Token::Str(content) if content == "a + b;"));
+        assert!(matches!(tokens[2], Token::EndOfStatement));
+    }
+
+    #[test]
+    fn string_keeps_its_whitespace() {
+        let tokens = lex("\"  hello  world \"");
+        assert!(matches!(&tokens[0], Token::Str(content) if content == "  hello  world "));
+    }
+
+    #[test]
+    fn unterminated_string_is_an_error() {
+        let tokens = lex("zipette \"hello;");
+        assert_eq!(tokens.len(), 2);
+        assert!(matches!(&tokens[1], Token::Bad(err) if err.message.contains("line 1, column 9")));
+    }
+
+    #[test]
+    fn hexadecimal_and_binary_literals() {
+        let tokens = lex("0xFF 0b1010 0Xa -0x10 0");
+        assert_eq!(tokens.len(), 6);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 255.0));
+        assert!(matches!(tokens[1], Token::Number(n) if n == 10.0));
+        assert!(matches!(tokens[2], Token::Number(n) if n == 10.0));
+        assert!(matches!(tokens[3], Token::Minus));
+        assert!(matches!(tokens[4], Token::Number(n) if n == 16.0));
+        assert!(matches!(tokens[5], Token::Number(n) if n == 0.0));
+    }
+
+    #[test]
+    fn invalid_radix_digits_are_rejected() {
+        let tokens = lex("0b102; 0xZ; 0x;");
+        assert_eq!(tokens.len(), 6);
+        assert!(matches!(&tokens[0], Token::Bad(err) if err.message == "Invalid digit '2' in number literal '0b102'"));
+        assert!(matches!(&tokens[2], Token::Bad(err) if err.message == "Invalid digit 'Z' in number literal '0xZ'"));
+        assert!(matches!(&tokens[4], Token::Bad(err) if err.message == "Missing digits in number literal '0x'"));
+    }
+
+    #[test]
+    fn scientific_notation() {
+        let tokens = lex("1e6 2.5e-3 1E+10 -4e2");
+        assert_eq!(tokens.len(), 5);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1e6));
+        assert!(matches!(tokens[1], Token::Number(n) if n == 2.5e-3));
+        assert!(matches!(tokens[2], Token::Number(n) if n == 1e10));
+        assert!(matches!(tokens[3], Token::Minus));
+        assert!(matches!(tokens[4], Token::Number(n) if n == 400.0));
+    }
+
+    #[test]
+    fn malformed_exponent_is_rejected() {
+        let tokens = lex("1e; 2e+;");
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(&tokens[0], Token::Bad(err) if err.message == "Missing exponent digits in number literal '1e'"));
+        assert!(matches!(&tokens[2], Token::Bad(err) if err.message == "Missing exponent digits in number literal '2e+'"));
+    }
+
+    #[test]
+    fn underscores_separate_digits() {
+        let tokens = lex("1_000_000 12.345_678 0xFF_FF 1e1_0");
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1_000_000.0));
+        assert!(matches!(tokens[1], Token::Number(n) if n == 12.345_678));
+        assert!(matches!(tokens[2], Token::Number(n) if n == 65535.0));
+        assert!(matches!(tokens[3], Token::Number(n) if n == 1e10));
+    }
+
+    #[test]
+    fn misplaced_underscores_are_rejected() {
+        for (source, literal) in [("100_", "100_"), ("1__0", "1__0"), ("1_.5", "1_.5"), ("0x_FF", "0x_FF")] {
+            let tokens = lex(source);
+            assert_eq!(tokens.len(), 1, "{source}");
+            let expected = format!("Invalid underscore placement in number literal '{literal}'");
+            assert!(matches!(&tokens[0], Token::Bad(err) if err.message == expected), "{source}");
+        }
+    }
+
+    #[test]
+    fn identifiers_may_contain_digits_and_underscores() {
+        let tokens = lex("total_2 _tmp x1 _100");
+        let names: Vec<&str> = tokens.iter()
+            .map(|t| match t {
+                Token::Identifier(id) => id.as_str(),
+                other => panic!("expected an identifier, got {:?}", other),
+            })
+            .collect();
+        assert_eq!(names, vec!["total_2", "_tmp", "x1", "_100"]);
+    }
+
+    #[test]
+    fn leading_digit_lexes_as_number_then_identifier() {
+        let tokens = lex("2abc");
+        assert_eq!(tokens.len(), 2);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 2.0));
+        assert!(matches!(&tokens[1], Token::Identifier(id) if id == "abc"));
+    }
+
+    #[test]
+    fn modulo_token() {
+        let tokens = lex("10 % 3");
+        assert!(matches!(tokens[1], Token::Modulo));
+    }
+
+    #[test]
+    fn comparison_tokens_are_disambiguated_from_shifts() {
+        let tokens = lex("a <= b >> 2 != c < d == e >= f > g << 1");
+        assert_eq!(tokens.len(), 17);
+        assert!(matches!(&tokens[0], Token::Identifier(id) if id == "a"));
+        assert!(matches!(tokens[1], Token::LessEqual));
+        assert!(matches!(&tokens[2], Token::Identifier(id) if id == "b"));
+        assert!(matches!(tokens[3], Token::BytesRight));
+        assert!(matches!(tokens[4], Token::Number(n) if n == 2.0));
+        assert!(matches!(tokens[5], Token::NotEqual));
+        assert!(matches!(&tokens[6], Token::Identifier(id) if id == "c"));
+        assert!(matches!(tokens[7], Token::Less));
+        assert!(matches!(&tokens[8], Token::Identifier(id) if id == "d"));
+        assert!(matches!(tokens[9], Token::Equal));
+        assert!(matches!(tokens[11], Token::GreaterEqual));
+        assert!(matches!(tokens[13], Token::Greater));
+        assert!(matches!(tokens[15], Token::BytesLeft));
+    }
+
+    #[test]
+    fn lone_equal_and_bang_are_not_comparisons() {
+        let tokens = lex("= !");
+        assert!(matches!(tokens[0], Token::Assign));
+        assert!(matches!(tokens[1], Token::Not));
+    }
+
+    #[test]
+    fn logical_operators_next_to_each_other() {
+        let tokens = lex("a&&!b||c");
+        assert_eq!(tokens.len(), 6);
+        assert!(matches!(&tokens[0], Token::Identifier(id) if id == "a"));
+        assert!(matches!(tokens[1], Token::And));
+        assert!(matches!(tokens[2], Token::Not));
+        assert!(matches!(&tokens[3], Token::Identifier(id) if id == "b"));
+        assert!(matches!(tokens[4], Token::Or));
+        assert!(matches!(&tokens[5], Token::Identifier(id) if id == "c"));
+    }
+
+    #[test]
+    fn single_ampersand_or_pipe_are_bitwise() {
+        let tokens = lex("a & b | c xor ~d");
+        assert_eq!(tokens.len(), 8);
+        assert!(matches!(tokens[1], Token::BitAnd));
+        assert!(matches!(tokens[3], Token::BitOr));
+        assert!(matches!(tokens[5], Token::BitXor));
+        assert!(matches!(tokens[6], Token::BitNot));
+    }
+
+    #[test]
+    fn compound_assignment_tokens() {
+        let tokens = lex("+= -= *= /= ** -1");
+        assert_eq!(tokens.len(), 7);
+        assert!(matches!(tokens[0], Token::PlusAssign));
+        assert!(matches!(tokens[1], Token::MinusAssign));
+        assert!(matches!(tokens[2], Token::StarAssign));
+        assert!(matches!(tokens[3], Token::SlashAssign));
+        assert!(matches!(tokens[4], Token::Exponent));
+        assert!(matches!(tokens[5], Token::Minus));
+        assert!(matches!(tokens[6], Token::Number(n) if n == 1.0));
+    }
+
+    #[test]
+    fn comma_is_decimal_only_before_a_digit() {
+        let tokens = lex("1,5");
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1.5));
+
+        let tokens = lex("1 , 5");
+        assert_eq!(tokens.len(), 3);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1.0));
+        assert!(matches!(tokens[1], Token::Comma));
+        assert!(matches!(tokens[2], Token::Number(n) if n == 5.0));
+
+        let tokens = lex("1,x");
+        assert_eq!(tokens.len(), 3);
+        assert!(matches!(tokens[0], Token::Number(n) if n == 1.0));
+        assert!(matches!(tokens[1], Token::Comma));
+        assert!(matches!(&tokens[2], Token::Identifier(id) if id == "x"));
+    }
+
+    #[test]
+    fn lookahead_peeks_without_skipping_or_duplicating() {
+        let mut tokens = Lookahead::new(lex("vicer x 3;").into_iter());
+        assert!(matches!(tokens.peek(), Some(Token::KwAssign)));
+        assert!(matches!(tokens.peek_nth(1), Some(Token::Identifier(id)) if id == "x"));
+        assert!(matches!(tokens.peek(), Some(Token::KwAssign)));
+        assert!(matches!(tokens.next(), Some(Token::KwAssign)));
+        assert!(matches!(tokens.peek_nth(1), Some(Token::Number(n)) if *n == 3.0));
+        assert!(matches!(tokens.next(), Some(Token::Identifier(id)) if id == "x"));
+        assert!(matches!(tokens.next(), Some(Token::Number(n)) if n == 3.0));
+        assert!(tokens.peek_nth(1).is_none());
+        assert!(matches!(tokens.peek(), Some(Token::EndOfStatement)));
+        assert!(matches!(tokens.next(), Some(Token::EndOfStatement)));
+        assert!(tokens.peek().is_none());
+        assert!(tokens.next().is_none());
+    }
+
+    #[test]
+    fn multiple_decimal_points_are_rejected() {
+        let tokens = lex("1.2.3; 1,2.3; 4.5,6;");
+        assert_eq!(tokens.len(), 6);
+        assert!(matches!(&tokens[0], Token::Bad(err) if err.message == "Invalid number literal '1.2.3'"));
+        assert!(matches!(&tokens[2], Token::Bad(err) if err.message == "Invalid number literal '1,2.3'"));
+        assert!(matches!(&tokens[4], Token::Bad(err) if err.message == "Invalid number literal '4.5,6'"));
+    }
+
+    #[test]
+    fn trailing_decimal_point_is_rejected() {
+        let tokens = lex("5.;");
+        assert_eq!(tokens.len(), 2);
+        assert!(matches!(&tokens[0], Token::Bad(err) if err.message == "Missing digits after the decimal point in number literal '5.'"));
+        assert!(matches!(tokens[1], Token::EndOfStatement));
+    }
+
+    #[test]
+    fn eof_is_emitted_once_at_the_end() {
+        let tokens: Vec<LocalizedToken> = Lexer::new("zipette 1;\n  ").localized().collect();
+        assert_eq!(tokens.len(), 4);
+        assert!(matches!(tokens[3].token, Token::Eof));
+        assert_eq!((tokens[3].loc.line, tokens[3].loc.start_column), (2, 3));
+
+        let tokens: Vec<LocalizedToken> = Lexer::new("").localized().collect();
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(tokens[0].token, Token::Eof));
+    }
+
+    #[test]
+    fn scan_all_reports_every_error() {
+        let (tokens, errors) = Lexer::scan_all("vicer x 1 @ 2;\nzipette 0b12 + 1.2.3;\n  zipette \"oops;");
+        let found: Vec<(usize, usize, usize, &str)> = errors.iter()
+            .map(|err| {
+                let loc = err.loc.as_ref().unwrap();
+                (loc.line, loc.start_column, loc.end_column, err.message.as_str())
+            })
+            .collect();
+        assert_eq!(found, vec![
+            (1, 11, 12, "Unexpected character '@'"),
+            (2, 9, 13, "Invalid digit '2' in number literal '0b12'"),
+            (2, 16, 21, "Invalid number literal '1.2.3'"),
+            (3, 11, 17, "Unterminated string opened at line 3, column 11"),
+        ]);
+        assert_eq!(tokens.iter().filter(|token| matches!(token, Token::Invalid)).count(), 4);
+        assert!(matches!(tokens[4], Token::Number(n) if n == 2.0));
+        assert!(matches!(tokens.last(), Some(Token::Eof)));
+    }
+
+    #[test]
+    fn scan_all_without_errors() {
+        let (tokens, errors) = Lexer::scan_all("zipette 1;");
+        assert!(errors.is_empty());
+        assert_eq!(tokens.len(), 4);
+    }
+
+    /// Hands out at most three bytes per read to exercise chunk boundaries.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn streaming_lexer_matches_in_memory_lexer() {
+        let source = "vicer total_amount 1_000.25; # comment\nzipette total_amount ** 2 /* spans\ntwo lines */ + 0xFF;\nzipette \"multi\nline\";\n  lsd red -3e2;";
+        let reader = std::io::BufReader::with_capacity(3, Trickle(source.as_bytes()));
+
+        let mut lexer = Lexer::from_reader(reader);
+        let streamed: Vec<String> = std::iter::from_fn(|| lexer.next_localized_token())
+            .map(|t| format!("{:?}", t))
+            .collect();
+        let in_memory: Vec<String> = Lexer::new(source).localized()
+            .map(|t| format!("{:?}", t))
+            .collect();
+        assert_eq!(streamed, in_memory);
+        assert!(streamed.last().unwrap().contains("Eof"));
+    }
+
+    #[test]
+    fn streaming_lexer_reports_unterminated_comment_at_end() {
+        let reader = std::io::BufReader::with_capacity(3, Trickle(b"zipette 1;\n/* never\nclosed"));
+        let mut lexer = Lexer::from_reader(reader);
+        let tokens: Vec<Token> = std::iter::from_fn(|| lexer.next_token()).collect();
+        assert_eq!(tokens.len(), 5);
+        assert!(matches!(&tokens[3], Token::Bad(err) if err.message.starts_with("Unterminated block comment")));
+        assert!(matches!(tokens[4], Token::Eof));
+    }
+
+    #[test]
+    fn tokens_display_as_they_appear_in_source() {
+        let rendered: Vec<String> = lex("3.5 foo \"hi\" ) ** ; << != && xor += , } ] zipette @ 0b2")
+            .iter()
+            .map(|token| token.to_string())
+            .collect();
+        assert_eq!(rendered, vec![
+            "number 3.5", "identifier 'foo'", "string \"hi\"", "')'", "'**'", "';'", "'<<'", "'!='",
+            "'&&'", "'xor'", "'+='", "','", "'}'", "']'", "keyword 'zipette'",
+            "unexpected character '@'", "invalid token (Invalid digit '2' in number literal '0b2')",
+        ]);
+        assert_eq!(Token::Eof.to_string(), "end of file");
+        assert_eq!(Token::Invalid.to_string(), "invalid token");
+    }
+
+    fn id(name: &str) -> Token {
+        Token::Identifier(name.to_string())
+    }
+
+    #[test]
+    fn full_sequence_for_assignment_and_print() {
+        assert_eq!(lex("vicer x 3; zipette x ** 2;"), vec![
+            Token::KwAssign, id("x"), Token::Number(3.0), Token::EndOfStatement,
+            Token::KwPrint, id("x"), Token::Exponent, Token::Number(2.0), Token::EndOfStatement,
+        ]);
+    }
+
+    #[test]
+    fn full_sequence_for_colored_print() {
+        assert_eq!(lex("lsd multi (1 + 2) * 3;"), vec![
+            Token::KwPrintColored, id("multi"), Token::OpenParen, Token::Number(1.0), Token::Plus,
+            Token::Number(2.0), Token::CloseParen, Token::Product, Token::Number(3.0), Token::EndOfStatement,
+        ]);
+    }
+
+    #[test]
+    fn full_sequence_for_shifts_and_comparisons() {
+        assert_eq!(lex("zipette 8 >> 1 <= 0b100 % 3;"), vec![
+            Token::KwPrint, Token::Number(8.0), Token::BytesRight, Token::Number(1.0), Token::LessEqual,
+            Token::Number(4.0), Token::Modulo, Token::Number(3.0), Token::EndOfStatement,
+        ]);
+    }
+
+    #[test]
+    fn full_sequence_for_block_with_comments() {
+        assert_eq!(lex("{ # start\n  total += 1,5; /* done */ }"), vec![
+            Token::OpenBrace, id("total"), Token::PlusAssign, Token::Number(1.5), Token::EndOfStatement, Token::CloseBrace,
+        ]);
+    }
+
+    #[test]
+    fn full_sequence_with_errors() {
+        assert_eq!(lex("zipette \"a\" @ 1e;"), vec![
+            Token::KwPrint,
+            Token::Str("a".to_string()),
+            Token::Useless('@'),
+            Token::Bad(LexerError {
+                message: "Missing exponent digits in number literal '1e'".to_string(),
+                loc: Some(Location { line: 1, start_column: 15, end_column: 17 }),
+            }),
+            Token::EndOfStatement,
+        ]);
+    }
+
+    #[test]
+    fn full_localized_sequence() {
+        assert_eq!(Lexer::new("x;").localized().collect::<Vec<_>>(), vec![
+            LocalizedToken { token: id("x"), loc: Location { line: 1, start_column: 1, end_column: 2 }, span: Span { start: 0, end: 1 } },
+            LocalizedToken { token: Token::EndOfStatement, loc: Location { line: 1, start_column: 2, end_column: 3 }, span: Span { start: 1, end: 2 } },
+            LocalizedToken { token: Token::Eof, loc: Location { line: 1, start_column: 3, end_column: 3 }, span: Span { start: 2, end: 2 } },
+        ]);
+    }
+
+    #[test]
+    fn arrow_is_recognized_after_minus() {
+        assert_eq!(lex("->"), vec![Token::Arrow]);
+        assert_eq!(lex("- >"), vec![Token::Minus, Token::Greater]);
+        assert_eq!(lex("-5"), vec![Token::Minus, Token::Number(5.0)]);
+        assert_eq!(lex("a->b"), vec![id("a"), Token::Arrow, id("b")]);
+        assert_eq!(lex("->5"), vec![Token::Arrow, Token::Number(5.0)]);
+        assert_eq!(lex("-->"), vec![Token::Minus, Token::Arrow]);
+    }
+
+    #[test]
+    fn colon_token() {
+        assert_eq!(lex("x: nombre"), vec![id("x"), Token::Colon, id("nombre")]);
+    }
+
+    #[test]
+    fn question_token() {
+        assert_eq!(lex("a ? b : c"), vec![id("a"), Token::Question, id("b"), Token::Colon, id("c")]);
+    }
+
+    #[test]
+    fn increment_and_decrement_tokens() {
+        assert_eq!(lex("i++ i--"), vec![id("i"), Token::Increment, id("i"), Token::Decrement]);
+        assert_eq!(lex("--5"), vec![Token::Decrement, Token::Number(5.0)]);
+        assert_eq!(lex("x-->0"), vec![id("x"), Token::Minus, Token::Arrow, Token::Number(0.0)]);
+        assert_eq!(lex("+ +"), vec![Token::Plus, Token::Plus]);
+    }
+
+    #[test]
+    fn comma_separator_is_not_glued_to_numbers() {
+        assert_eq!(lex("1,5"), vec![Token::Number(1.5)]);
+        assert_eq!(lex("1 ,5"), vec![Token::Number(1.0), Token::Comma, Token::Number(5.0)]);
+        assert_eq!(lex("1, 5"), vec![Token::Number(1.0), Token::Comma, Token::Number(5.0)]);
+        assert_eq!(lex("1,,5"), vec![Token::Number(1.0), Token::Comma, Token::Comma, Token::Number(5.0)]);
+        assert_eq!(lex("1,"), vec![Token::Number(1.0), Token::Comma]);
+    }
+
+    #[test]
+    fn spans_slice_back_to_the_token_text() {
+        let source = "vicer x 2 ** 3; # note\nzipette x << 1_000 \"hé\" 1.2.3;";
+        let texts: Vec<&str> = Lexer::new(source)
+            .localized()
+            .map(|localized| &source[localized.span.start..localized.span.end])
+            .collect();
+        assert_eq!(texts, vec!["vicer", "x", "2", "**", "3", ";", "zipette", "x", "<<", "1_000", "\"hé\"", "1.2.3", ";", ""]);
+    }
+
+    #[test]
+    fn streaming_spans_match_the_whole_source() {
+        let source = "vicer total 12;\nzipette total ** 2;\n";
+        let mut lexer = Lexer::from_reader(std::io::BufReader::with_capacity(3, Trickle(source.as_bytes())));
+        let mut spans = Vec::new();
+        while let Some(localized) = lexer.next_localized_token() {
+            spans.push(localized.span);
+        }
+        let expected: Vec<Span> = Lexer::new(source).localized().map(|localized| localized.span).collect();
+        assert_eq!(spans, expected);
+    }
+
+    #[test]
+    fn minus_is_never_glued_to_a_number() {
+        assert_eq!(lex("x -5"), vec![id("x"), Token::Minus, Token::Number(5.0)]);
+        assert_eq!(lex("3-2"), vec![Token::Number(3.0), Token::Minus, Token::Number(2.0)]);
+    }
+
+    #[test]
+    fn bom_and_crlf_lex_like_plain_lf() {
+        let lf = "vicer x 1;\nzipette x @;\n";
+        let windows = "\u{feff}vicer x 1;\r\nzipette x @;\r\n";
+        let locations = |source| Lexer::new(source)
+            .localized()
+            .map(|localized| (localized.token, localized.loc))
+            .collect::<Vec<_>>();
+        assert_eq!(locations(windows), locations(lf));
+
+        let mut streamed = Vec::new();
+        let mut lexer = Lexer::from_reader(std::io::BufReader::with_capacity(3, Trickle(windows.as_bytes())));
+        while let Some(localized) = lexer.next_localized_token() {
+            streamed.push((localized.token, localized.loc));
+        }
+        assert_eq!(streamed, locations(lf));
+    }
+
+    #[test]
+    fn bom_is_only_skipped_at_the_start() {
+        assert_eq!(lex("x \u{feff}"), vec![id("x"), Token::Useless('\u{feff}')]);
+    }
+
+    #[test]
+    fn tab_width_expands_columns() {
+        let first_column = |width| Lexer::new("\t\tzipette 1;").with_tab_width(width).next_localized_token().unwrap().loc.start_column;
+        assert_eq!(first_column(1), 3);
+        assert_eq!(first_column(4), 9);
+        assert_eq!(first_column(8), 17);
+
+        let loc = Lexer::new("ab\tc").with_tab_width(4).localized().nth(1).unwrap().loc;
+        assert_eq!(loc, Location { line: 1, start_column: 5, end_column: 6 });
+    }
+
+    /// Rough throughput check, run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_lex_one_megabyte() {
+        let mut source = String::new();
+        let mut i = 0;
+        while source.len() < 1_000_000 {
+            source += &format!("vicer variable_{i} {i}_000.25e-3 ** (total + 0x{i:x}); # step {i}\nzipette \"ligne\" ;\n");
+            i += 1;
+        }
+        let started = std::time::Instant::now();
+        let count = Lexer::new(&source).localized().count();
+        println!("lexed {} bytes into {} tokens in {:?}", source.len(), count, started.elapsed());
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_lex_five_megabytes_mixed() {
+        let mut source = String::new();
+        let mut i = 0;
+        while source.len() < 5_000_000 {
+            source += &format!("vicer compteur_{i}   {i}.5;\t# où était-il ?\nzipette \"résultat ${{compteur_{i}}}\" ;\n\n");
+            i += 1;
+        }
+        let started = std::time::Instant::now();
+        let count = Lexer::new(&source).localized().count();
+        println!("lexed {} bytes into {} tokens in {:?}", source.len(), count, started.elapsed());
+    }
+
+    #[test]
+    fn interpolated_strings() {
+        assert_eq!(lex("\"total = ${total}!\""), vec![Token::InterpolatedStr(vec![
+            StrPart::Text("total = ".to_string()),
+            StrPart::Expr("total".to_string()),
+            StrPart::Text("!".to_string()),
+        ])]);
+        assert_eq!(lex("\"${a}${b + 1}\""), vec![Token::InterpolatedStr(vec![
+            StrPart::Expr("a".to_string()),
+            StrPart::Expr("b + 1".to_string()),
+        ])]);
+        assert_eq!(lex("\"5$ {x}\""), vec![Token::Str("5$ {x}".to_string())]);
+        assert_eq!(Token::InterpolatedStr(vec![StrPart::Text("x = ".to_string()), StrPart::Expr("x".to_string())]).to_string(), "string \"x = ${x}\"");
+    }
+
+    #[test]
+    fn unclosed_interpolation_points_at_the_opening_brace() {
+        assert_eq!(lex("\"a ${b\"; x"), vec![
+            Token::Bad(LexerError {
+                message: "Unclosed '${' at line 1, column 4".to_string(),
+                loc: Some(Location { line: 1, start_column: 4, end_column: 6 }),
+            }),
+            Token::EndOfStatement,
+            id("x"),
+        ]);
+    }
+
+    #[test]
+    fn fast_path_handles_accented_text() {
+        let source = "vicer café_1 2;\u{a0}zipette \"été ${café_1}\"; # où\n\u{2003}naïve;";
+        let localized: Vec<LocalizedToken> = Lexer::new(source).localized().collect();
+        for token in &localized {
+            assert!(source.is_char_boundary(token.span.start) && source.is_char_boundary(token.span.end));
+        }
+        let tokens: Vec<Token> = localized.iter().map(|localized| localized.token.clone()).collect();
+        assert_eq!(tokens, vec![
+            Token::KwAssign, id("caf"), Token::Useless('é'), id("_1"), Token::Number(2.0), Token::EndOfStatement,
+            Token::KwPrint,
+            Token::InterpolatedStr(vec![StrPart::Text("été ".to_string()), StrPart::Expr("café_1".to_string())]),
+            Token::EndOfStatement,
+            id("na"), Token::Useless('ï'), id("ve"), Token::EndOfStatement,
+            Token::Eof,
+        ]);
+        let columns: Vec<usize> = localized.iter().map(|localized| localized.loc.start_column).collect();
+        assert_eq!(columns, vec![1, 7, 10, 11, 14, 15, 17, 25, 40, 2, 4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn shebang_line_is_skipped() {
+        let first = |source| Lexer::new(source).next_localized_token().unwrap();
+        let token = first("#!/usr/bin/env zipette-interpreter\nzipette 1;");
+        assert_eq!(token.token, Token::KwPrint);
+        assert_eq!(token.loc, Location { line: 2, start_column: 1, end_column: 8 });
+        assert_eq!(first("\u{feff}#!zipette\r\nx").loc.line, 2);
+        assert_eq!(first("#!zipette").token, Token::Eof);
+    }
+
+    #[test]
+    fn render_token_dump() {
+        let source = "vicer x 2.5;\nzipette \"x=${x}\" @ x ** 1e;\n";
+        assert_eq!(render(&dump(source)), "\
+1:1     KwAssign         'vicer'
+1:7     Identifier       'x'
+1:9     Number           '2.5'
+1:12    EndOfStatement   ';'
+2:1     KwPrint          'zipette'
+2:9     InterpolatedStr  '\"x=${x}\"'
+2:18    Useless          '@'
+2:20    Identifier       'x'
+2:22    Exponent         '**'
+2:25    Bad              'Missing exponent digits in number literal '1e''
+2:27    EndOfStatement   ';'
+3:1     Eof              ''
+");
+    }
+
+    #[test]
+    fn boolean_keywords() {
+        assert_eq!(lex("vrai faux vraiment"), vec![Token::KwTrue, Token::KwFalse, id("vraiment")]);
+        assert_eq!(Token::KwTrue.to_string(), "keyword 'vrai'");
+    }
+
+    #[test]
+    fn numbers_from_text() {
+        assert_eq!(number("42"), Some(42.0));
+        assert_eq!(number(" 3,5\n"), Some(3.5));
+        assert_eq!(number("-2.25"), Some(-2.25));
+        assert_eq!(number("1_000"), Some(1000.0));
+        assert_eq!(number("0x1F"), Some(31.0));
+        assert_eq!(number(""), None);
+        assert_eq!(number("abc"), None);
+        assert_eq!(number("1 2"), None);
+        assert_eq!(number("--1"), None);
     }
 }
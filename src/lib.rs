@@ -0,0 +1,7 @@
+pub mod lexer;
+pub mod parser;
+pub mod interpreter;
+pub mod builtins;
+pub mod json;
+pub mod sexpr;
+pub mod fold;
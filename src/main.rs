@@ -1,27 +1,33 @@
 use std::env;
-use std::fmt::format;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 use colored::Colorize;
-use crate::interpreter::Interpreter;
-use crate::lexer::Lexer;
-use crate::parser::{Parser};
-
-mod lexer;
-mod parser;
-mod interpreter;
+use my_interpreter::fold::fold_constants;
+use my_interpreter::interpreter::Interpreter;
+use my_interpreter::lexer::{Lexer, Location};
+use my_interpreter::parser::{Parser};
 
 const DEFAULT_FILE: &str = "quartier";
 const EXTENSION: &str = "zipette";
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+/// Columns a tab spans in error positions and in the source lines shown
+/// with them.
+const TAB_WIDTH: usize = 4;
 
 fn main() {
     let mut args: Vec<String> = env::args().collect();
+    // Everything after `--` goes to the script, even what looks like a flag.
+    let mut script_args = match args.iter().position(|arg| arg == "--") {
+        Some(dashes) => args.split_off(dashes).split_off(1),
+        None => Vec::new(),
+    };
+    let fold = take_flag(&mut args, "--fold");
 
     if args.len() <= 1 {
         args.insert(1, format!("{DEFAULT_FILE}.{EXTENSION}"));
     }
+    script_args.splice(0..0, args.drain(2..));
 
     if !&args[1].ends_with(EXTENSION) {
         println!("{}", format!("File must be a .{EXTENSION} file.").red());
@@ -51,25 +57,62 @@ fn main() {
         }
     }
 
-
-    let lex = Lexer::new(file_content.as_str());
-
-    &lex.for_each(|c| { dbg!(c); });
-
-    let lex = Lexer::new(file_content.as_str());
-
-
-    let mut parser = Parser::new(lex);
+    let mut parser = Parser::new(Lexer::new(&file_content).with_tab_width(TAB_WIDTH).localized());
 
     println!("{}", format!("======= ZipetteInterpreter v{VERSION} =======").on_cyan());
 
-    match parser.parse() {
-        Ok(program) => {
-            Interpreter::new(program).interpret();;
+    match parser.parse_all() {
+        Ok(mut program) => {
+            if fold {
+                fold_constants(&mut program);
+            }
+            std::process::exit(Interpreter::new(program).with_path(filename).with_tab_width(TAB_WIDTH).with_args(script_args).interpret());
         },
-        Err(err) => {
-            println!("{}", err.to_string().red());
+        // Lexer errors come along with the parse errors, each with its position.
+        Err(errors) => {
+            for err in errors {
+                println!("{}", err.to_string().red());
+                if let Some(loc) = &err.loc {
+                    print_source_line(&file_content, loc);
+                }
+            }
+            std::process::exit(1);
         },
     };
 
-}
\ No newline at end of file
+}
+
+/// Removes `flag` from the arguments, telling whether it was there.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let count = args.len();
+    args.retain(|arg| arg != flag);
+    args.len() != count
+}
+
+/// Shows the source line at `loc` with a caret under the offending token.
+fn print_source_line(source: &str, loc: &Location) {
+    let Some(line) = source.lines().nth(loc.line - 1) else {
+        return;
+    };
+    let width = loc.end_column.saturating_sub(loc.start_column).max(1);
+    println!("{:>4} | {}", loc.line, expand_tabs(line));
+    println!("     | {}{}", " ".repeat(loc.start_column - 1), "^".repeat(width).red());
+}
+
+/// Replaces the tabs of `line` with spaces up to the next multiple of
+/// `TAB_WIDTH`, so that its columns are the ones the lexer reports.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - column % TAB_WIDTH;
+            expanded.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
@@ -1,33 +1,92 @@
-use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Duration;
 use colored::Colorize;
 use rand::Rng;
-use crate::lexer::Token;
+use crate::builtins;
+use crate::interpreter::{Environment, Value, MAX_CALL_DEPTH};
+pub use crate::json::to_json;
+use crate::lexer::{self, Lexer, LexerError, Location, Lookahead, ParserInput, StrPart, Token};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryExpressionType {
     Sum,
     Product,
     Division,
+    Modulo,
     Minus,
     Exponent,
     BytesLeft,
     BytesRight,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnaryOp {
+    BitNot,
+    Neg,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Number(f64),
+    Str(String),
+    Array(Vec<Expression>),
     Identifier(String),
     Binary {
         op: BinaryExpressionType,
         left: Box<Expression>,
         right: Box<Expression>
     },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expression>
+    },
+    Index {
+        target: Box<Expression>,
+        index: Box<Expression>
+    },
+    Conditional {
+        cond: Box<Expression>,
+        then: Box<Expression>,
+        otherwise: Box<Expression>
+    },
+    Logical {
+        op: LogicalOp,
+        left: Box<Expression>,
+        right: Box<Expression>
+    },
+    Call {
+        name: String,
+        args: Vec<Expression>
+    },
+    /// A string literal with `${...}` in it, evaluating to a texte.
+    Interpolation(Vec<StringPart>),
+    /// `hasard`, a number drawn uniformly from [0, 1), or from [min, max)
+    /// for `hasard(min, max)`.
+    Random(Option<Box<[Expression; 2]>>),
+    /// `horloge`, the milliseconds elapsed since the program started.
+    Clock,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Colored {
     Red,
     Blue,
@@ -39,13 +98,41 @@ pub enum Colored {
     White,
     Brown,
     Pink,
-    MultiColor
+    MultiColor,
+    /// Any color given by its red, green and blue components.
+    Custom(u8, u8, u8),
 }
 
 impl Colored {
-    fn random() -> Self {
-        let mut rng = rand::rng();
-        match rng.random_range(0..=9) {
+    /// The English color names, one per variant; see `Colored::from_name`.
+    const NAMES: [&'static str; 11] = ["red", "blue", "green", "yellow", "purple", "cyan", "orange", "white", "brown", "pink", "multicolor"];
+
+    /// Looks up a color by its English or French name, as written after `lsd`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "red" | "rouge" => Some(Colored::Red),
+            "blue" | "bleu" => Some(Colored::Blue),
+            "green" | "vert" => Some(Colored::Green),
+            "yellow" | "jaune" => Some(Colored::Yellow),
+            "purple" | "violet" => Some(Colored::Purple),
+            "cyan" => Some(Colored::Cyan),
+            "orange" => Some(Colored::Orange),
+            "white" | "blanc" => Some(Colored::White),
+            "brown" | "marron" => Some(Colored::Brown),
+            "pink" | "rose" => Some(Colored::Pink),
+            "multicolor" | "multi" | "multicolore" => Some(Colored::MultiColor),
+            _ => None,
+        }
+    }
+
+    fn random(rng: &mut impl Rng) -> Self {
+        Colored::from_index(rng.random_range(0..=9))
+    }
+
+    /// The color numbered `index` for `lsd (c) x;`, from 0 (red) to 10
+    /// (multicolor).
+    fn from_index(index: usize) -> Self {
+        match index {
             0 => Colored::Red,
             1 => Colored::Blue,
             2 => Colored::Green,
@@ -56,265 +143,2972 @@ impl Colored {
             7 => Colored::White,
             8 => Colored::Brown,
             9 => Colored::Pink,
+            10 => Colored::MultiColor,
             _ => unreachable!()
         }
     }
 }
 
-#[derive(Debug)]
+/// Type names accepted in `vicer name: type value;` annotations.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueType {
+    Number,
+}
+
+impl ValueType {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nombre" => Some(ValueType::Number),
+            _ => None,
+        }
+    }
+}
+
+/// A piece of an interpolated string, see `Expression::Interpolation`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Expression(Expression),
-    Print(Expression),
-    PrintColored(Colored,Expression),
+    /// Prints its values on one line, separated by a space.
+    Print(Vec<Expression>),
+    /// Like `Print`, without the trailing newline.
+    Write(Vec<Expression>),
+    /// Reads a number from the input into `name`, after printing `prompt`.
+    Input {
+        name: String,
+        prompt: Option<Expression>
+    },
+    /// `lsd red x;`, with the color name as written (`rouge` or `red`) to
+    /// print the statement back.
+    PrintColored(Colored, String, Vec<Expression>),
+    /// `lsd` with a color computed at runtime, see `Colored::from_index`.
+    PrintColoredDyn(Expression, Vec<Expression>),
+    /// `lsd rgb(r, g, b) x;`, the components being evaluated at runtime.
+    PrintColoredRgb(Box<[Expression; 3]>, Vec<Expression>),
     Assignment(String, Expression),
+    /// `name += value;`, or `-=`, `*=` and `/=`, assigning `name op value`.
+    CompoundAssignment(String, BinaryExpressionType, Expression),
+    /// `name++;` or `name--;`, adding or taking 1 from `name` depending on
+    /// `op`, `Sum` or `Minus`. `prefix` is for `++name;` and `--name;`.
+    Step {
+        name: String,
+        op: BinaryExpressionType,
+        prefix: bool
+    },
+    /// `fixe name value;`, a variable that cannot be assigned again.
+    Constant(String, Expression),
+    /// `oublie name;`, removing the variable so that reading it fails.
+    Forget(String),
+    /// `importe "file.zipette";`, running that file in the current scope.
+    Import(String),
+    /// Stops the program with an error if the expression is false.
+    Assert(Expression),
+    /// Stops the program with an exit code, 0 when there is no expression.
+    Exit(Option<Expression>),
+    /// Pauses for a number of milliseconds.
+    Sleep(Expression),
+    TypedAssignment(String, ValueType, Expression),
+    Block(Vec<Statement>),
+    If {
+        cond: Expression,
+        then_block: Vec<Statement>,
+        else_block: Option<Vec<Statement>>
+    },
+    While {
+        cond: Expression,
+        body: Vec<Statement>
+    },
+    /// `fais { ... } tantque cond;`, checking `cond` after each iteration.
+    DoWhile {
+        body: Vec<Statement>,
+        cond: Expression
+    },
+    /// `boucle count { ... }`, with the iteration number in `indice`.
+    Repeat {
+        count: Expression,
+        body: Vec<Statement>
+    },
+    /// `selon value { 1: { ... } autre: { ... } }`, running the block of the
+    /// first arm equal to `value`, else the `autre` block if there is one.
+    Match {
+        subject: Expression,
+        arms: Vec<(Expression, Vec<Statement>)>,
+        default: Option<Vec<Statement>>
+    },
+    FunctionDef(Rc<Function>),
+    Return(Expression),
+    Break,
+    Continue,
+    /// A statement with the comments written on the lines above it and the
+    /// one after it on its line, kept when the lexer produces comments (see
+    /// `Lexer::with_comments`). It runs like the statement alone.
+    Commented {
+        comments: Vec<String>,
+        statement: Box<Statement>,
+        trailing: Option<String>
+    },
+    /// Comments on their own lines with no statement after them, at the end
+    /// of a block or of the program. Running them does nothing.
+    Comments(Vec<String>),
+}
+
+/// A user-defined function, shared between its definition and the
+/// interpreter once the definition has run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub body: Vec<Statement>,
+}
+
+impl Function {
+    /// How many arguments a call must at least pass: the parameters before
+    /// the first one with a default.
+    pub fn required_params(&self) -> usize {
+        self.params.iter().take_while(|param| param.default.is_none()).count()
+    }
+}
+
+/// A function parameter, with the expression that gives its value when a
+/// call leaves it out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub default: Option<Expression>,
+}
+
+/// How a statement finished: normally, or by a `renvoie` unwinding up to the
+/// enclosing function call or the top of the program, a `casse` or
+/// `continue` unwinding up to the enclosing loop, or a `quitte` unwinding
+/// the whole program.
+#[derive(Debug, PartialEq)]
+pub enum ControlFlow {
+    Normal,
+    Return(Value),
+    Break,
+    Continue,
+    Exit(i32),
 }
 
 #[derive(Debug)]
-pub struct ParseError(String);
+pub struct ParseError {
+    pub message: String,
+    /// Position of the token the parser was looking at, when the tokens
+    /// came with one.
+    pub loc: Option<Location>,
+}
+
+impl ParseError {
+    fn new(message: String) -> Self {
+        Self { message, loc: None }
+    }
+
+    /// Attaches `loc` unless the error already knows where it happened.
+    fn at(mut self, loc: Option<Location>) -> Self {
+        if self.loc.is_none() {
+            self.loc = loc;
+        }
+        self
+    }
+}
 
 impl Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[PARSER] Error : {}", self.0)
+        match &self.loc {
+            Some(loc) => write!(f, "[PARSER] Error at line {}, column {}: {}", loc.line, loc.start_column, self.message),
+            None => write!(f, "[PARSER] Error : {}", self.message),
+        }
     }
 }
 
 impl Error for ParseError {}
 
-pub struct Parser<I: Iterator<Item = Token>> {
-    tokens: I,
+impl From<LexerError> for ParseError {
+    fn from(err: LexerError) -> Self {
+        let loc = err.loc.clone();
+        ParseError { message: err.to_string(), loc }
+    }
+}
+
+pub struct Parser<I: Iterator<Item: ParserInput>> {
+    tokens: Lookahead<I>,
     current: Option<Token>,
+    /// Where `current` was read, kept when `current` is taken out.
+    current_loc: Option<Location>,
+    /// `{` read so far without their `}`, for error recovery to skip a
+    /// failed statement up to its end even when the error is in a block.
+    open_braces: usize,
+    ignore_keyword_case: bool,
+    /// Loops enclosing the statement being parsed, within the current function.
+    loop_depth: usize,
+    /// Comments read since the last statement, each with whether it stood on
+    /// its own line.
+    comments: Vec<(String, bool)>,
 }
 
-impl<I: Iterator<Item=Token>> Parser<I> {
-    pub fn new(mut tokens: I) -> Self {
-        let current = tokens.next();
-        Self { tokens, current }
+impl<I: Iterator<Item: ParserInput>> Parser<I> {
+    pub fn new(tokens: I) -> Self {
+        Self { tokens: Lookahead::new(tokens), current: None, current_loc: None, open_braces: 0, ignore_keyword_case: false, loop_depth: 0, comments: Vec::new() }
+    }
+
+    /// Lenient mode: keywords and `lsd` color names match regardless of case
+    /// (`ZIPETTE 3;`), while variable names stay case-sensitive.
+    pub fn ignore_keyword_case(mut self) -> Self {
+        self.ignore_keyword_case = true;
+        self
     }
 
     pub fn parse(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.consume()?;
         let mut statements = Vec::new();
-        while let Some(token) = &self.current {
-            if let Token::Identifier(id) = token {
-                statements.push(match id.as_str() {
-                    "zipette" => {
-                        self.consume();
-                       Statement::Print(self.parse_expression())
-                    },
-                    "lsd" => {
-                        if let Some(Token::Identifier(token)) = self.tokens.next() {
-                            self.consume();
-                            let color = match token.as_str() {
-                                "red" => Colored::Red,
-                                "blue" => Colored::Blue,
-                                "green" => Colored::Green,
-                                "yellow" => Colored::Yellow,
-                                "multicolor" | "multi" => Colored::MultiColor,
-                                _ => return Err(ParseError(format!("Unrecognised color type '{}'", token)))
-                            };
-
-                            Statement::PrintColored(color, self.parse_expression())
-                        } else {
-                            return Err(ParseError("Unexpected end of statement (; required)".to_string()));
-                        }
-                    },
-                    "vicer" => {
-                        if let Some(Token::Identifier(token)) = self.tokens.next() {
-                            self.consume();
-                            Statement::Assignment(token, self.parse_expression())
-                        } else {
-                            return Err(ParseError("Unexpected variable name".to_string()));
-                        }
-                    },
-                    _ => return Err(ParseError(format!("Unexpected identifier '{}'", id)))
-                });
-            } else {
-                statements.push(Statement::Expression(self.parse_expression()))
+        while !self.at_end() {
+            if let Some(Token::EndOfStatement) = self.current {
+                self.consume()?;
+                continue;
             }
+            let statement = self.parse_statement().map_err(|err| err.at(self.current_loc.clone()))?;
+            statements.push(statement);
+        }
+        self.push_pending_comments(&mut statements);
+        Ok(statements)
+    }
 
-            if !matches!(self.current, Some(Token::EndOfStatement)) {
-                return Err(ParseError("Unexpected end of statement (; required)".to_string()));
+    /// Parses the whole program like `parse`, but instead of stopping at the
+    /// first error skips to the next `;` and keeps going, so that every
+    /// mistake of the file is reported at once.
+    pub fn parse_all(&mut self) -> Result<Vec<Statement>, Vec<ParseError>> {
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        self.advance(&mut errors);
+        while !self.at_end() {
+            if let Some(Token::EndOfStatement) = self.current {
+                self.advance(&mut errors);
+                continue;
+            }
+            match self.parse_statement() {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    errors.push(err.at(self.current_loc.clone()));
+                    self.synchronize(&mut errors);
+                }
             }
-            self.consume();
-
         }
-        Ok(statements)
+        self.push_pending_comments(&mut statements);
+        if errors.is_empty() { Ok(statements) } else { Err(errors) }
     }
 
-    fn consume(&mut self) {
-        self.current = self.tokens.next();
+    /// Ends `statements` with the comments read since the last of them, see
+    /// `Statement::Comments`.
+    fn push_pending_comments(&mut self, statements: &mut Vec<Statement>) {
+        if !self.comments.is_empty() {
+            statements.push(Statement::Comments(self.comments.drain(..).map(|(text, _)| text).collect()));
+        }
     }
 
-    pub fn parse_expression(&mut self) -> Expression {
-        self.term_expression()
+    /// Skips the rest of the statement that failed: the tokens up to and
+    /// including the next `;` outside of any block, or the `}` closing the
+    /// outermost block (and its `;`).
+    fn synchronize(&mut self, errors: &mut Vec<ParseError>) {
+        while !self.at_end() {
+            let outside_blocks = self.open_braces == 0;
+            match self.current {
+                Some(Token::EndOfStatement) if outside_blocks => {
+                    self.advance(errors);
+                    return;
+                }
+                Some(Token::CloseBrace) if outside_blocks => {
+                    self.advance(errors);
+                    if let Some(Token::EndOfStatement) = self.current {
+                        self.advance(errors);
+                    }
+                    return;
+                }
+                _ => self.advance(errors),
+            }
+        }
     }
 
-    fn term_expression(&mut self) -> Expression {
-        let mut left = self.factor_expression();
-        while let Some(token) = &self.current {
-            let op = match token {
-                Token::Plus => BinaryExpressionType::Sum,
-                Token::Minus => BinaryExpressionType::Minus,
-                _ => break,
-            };
+    /// Consumes a token, recording the lexer errors met on the way.
+    fn advance(&mut self, errors: &mut Vec<ParseError>) {
+        while let Err(err) = self.consume() {
+            errors.push(err);
+        }
+    }
 
-            self.consume();
-            left = Expression::Binary {
-                op,
-                left: Box::new(left),
-                right: Box::new(self.factor_expression())
-            };
+    /// Parses a statement along with the comments around it, see
+    /// `Statement::Commented`. A comment alone on its line goes with the
+    /// statement after it, or ends the block or program it is in if none
+    /// follows (see `Statement::Comments`). Of the comments among the code,
+    /// the last one stays at the end of the line and the others go above.
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        let mut comments: Vec<String> = self.comments.drain(..).map(|(text, _)| text).collect();
+        let statement = self.parse_bare_statement()?;
+        let inline = self.comments.iter().take_while(|(_, own_line)| !own_line).count();
+        let mut inline: Vec<String> = self.comments.drain(..inline).map(|(text, _)| text).collect();
+        let trailing = inline.pop();
+        comments.extend(inline);
+        if comments.is_empty() && trailing.is_none() {
+            return Ok(statement);
         }
-        left
+        Ok(Statement::Commented { comments, statement: Box::new(statement), trailing })
     }
 
-    fn factor_expression(&mut self) -> Expression {
-        let mut left = self.exponent_expression();
-        while let Some(token) = &self.current {
-            let op = match token {
-                Token::Product => BinaryExpressionType::Product,
-                Token::Division => BinaryExpressionType::Division,
-                Token::BytesLeft => BinaryExpressionType::BytesLeft,
-                Token::BytesRight => BinaryExpressionType::BytesRight,
-                _ => break,
-            };
+    fn parse_bare_statement(&mut self) -> Result<Statement, ParseError> {
+        let statement = match &self.current {
+            Some(Token::KwPrint) => {
+                self.consume()?;
+                match self.take_current() {
+                    // `zipette;` prints an empty line.
+                    Some(Token::EndOfStatement) => {
+                        self.current = Some(Token::EndOfStatement);
+                        Statement::Print(Vec::new())
+                    }
+                    token => {
+                        self.current = token;
+                        Statement::Print(self.parse_values()?)
+                    }
+                }
+            },
+            Some(Token::KwSleep) => {
+                self.consume()?;
+                Statement::Sleep(self.parse_expression()?)
+            },
+            Some(Token::KwExit) => {
+                self.consume()?;
+                match self.current {
+                    Some(Token::EndOfStatement) => Statement::Exit(None),
+                    _ => Statement::Exit(Some(self.parse_expression()?)),
+                }
+            },
+            Some(Token::KwAssert) => {
+                self.consume()?;
+                Statement::Assert(self.parse_expression()?)
+            },
+            Some(Token::KwConst) => {
+                self.consume()?;
+                let name = match &self.current {
+                    Some(Token::Identifier(name)) => name.clone(),
+                    Some(token) if token.keyword_name().is_some() => return Err(reserved_keyword(token)),
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a constant name".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a constant name after 'fixe', found {}", token))),
+                };
+                self.consume()?;
+                Statement::Constant(name, self.parse_expression()?)
+            },
+            Some(Token::KwInput) => {
+                self.consume()?;
+                let name = match &self.current {
+                    Some(Token::Identifier(name)) => name.clone(),
+                    Some(token) if token.keyword_name().is_some() => return Err(reserved_keyword(token)),
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a variable name".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a variable name after 'demande', found {}", token))),
+                };
+                self.consume()?;
+                let prompt = match self.current {
+                    Some(Token::EndOfStatement) => None,
+                    _ => Some(self.parse_expression()?),
+                };
+                Statement::Input { name, prompt }
+            },
+            Some(Token::KwForget) => {
+                self.consume()?;
+                let name = match &self.current {
+                    Some(Token::Identifier(name)) => name.clone(),
+                    Some(token) if token.keyword_name().is_some() => return Err(reserved_keyword(token)),
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a variable name".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a variable name after 'oublie', found {}", token))),
+                };
+                self.consume()?;
+                Statement::Forget(name)
+            },
+            Some(Token::KwImport) => {
+                self.consume()?;
+                let file = match &self.current {
+                    Some(Token::Str(file)) => file.clone(),
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a file path".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a file path after 'importe', found {}", token))),
+                };
+                self.consume()?;
+                Statement::Import(file)
+            },
+            Some(Token::KwWrite) => {
+                self.consume()?;
+                Statement::Write(self.parse_values()?)
+            },
+            Some(Token::KwPrintColored) => {
+                self.consume()?;
+                let called = matches!(self.peek(), Some(Token::OpenParen));
+                let indexed = matches!(self.peek(), Some(Token::OpenBracket));
+                match &self.current {
+                    Some(Token::Identifier(name)) if name == "rgb" && called => {
+                        self.consume()?;
+                        let args = self.parse_call_args("rgb")?;
+                        let components = <[Expression; 3]>::try_from(args)
+                            .map_err(|args| ParseError::new(format!("'rgb' expects 3 components (red, green, blue), got {}", args.len())))?;
+                        Statement::PrintColoredRgb(Box::new(components), self.parse_values()?)
+                    }
+                    // Known color names (`lsd red (1+2);`) and other bare names
+                    // are color names, while a call or an index computes the
+                    // color like any other expression.
+                    Some(Token::Identifier(token)) if Colored::from_name(&token.to_lowercase()).is_some() || !(called || indexed) => {
+                        let name = if self.ignore_keyword_case { token.to_lowercase() } else { token.clone() };
+                        let color = Colored::from_name(&name).ok_or_else(|| ParseError::new(format!(
+                            "Unrecognised color type '{}' (expected one of {})", token, Colored::NAMES.join(", "))))?;
+                        let written = token.clone();
+                        self.consume()?;
 
-            self.consume();
-            left = Expression::Binary {
-                op,
-                left: Box::new(left),
-                right: Box::new(self.exponent_expression())
-            };
+                        Statement::PrintColored(color, written, self.parse_values()?)
+                    }
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a color name".to_string())),
+                    // Only a primary expression, so that in `lsd (c) -5;` the
+                    // color is `c` and -5 is printed.
+                    _ => {
+                        let color = self.parse_literal()?;
+                        Statement::PrintColoredDyn(color, self.parse_values()?)
+                    }
+                }
+            },
+            Some(Token::KwAssign) => {
+                self.consume()?;
+                match &self.current {
+                    Some(Token::Identifier(name)) => {
+                        let name = name.clone();
+                        self.consume()?;
+                        self.parse_assignment(name)?
+                    }
+                    Some(token) if token.keyword_name().is_some() => return Err(reserved_keyword(token)),
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a variable name".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a variable name, found {}", token))),
+                }
+            },
+            Some(Token::Increment | Token::Decrement) => {
+                let op = step_operator(&self.current);
+                self.consume()?;
+                match &self.current {
+                    Some(Token::Identifier(name)) => {
+                        let name = name.clone();
+                        self.consume()?;
+                        Statement::Step { name, op, prefix: true }
+                    }
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a variable name".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a variable name after '++' or '--', found {}", token))),
+                }
+            },
+            Some(Token::Identifier(id)) => {
+                let name = id.clone();
+                let loc = self.current_loc.clone();
+                self.consume()?;
+                if let Some(Token::Increment | Token::Decrement) = self.current {
+                    let op = step_operator(&self.current);
+                    self.consume()?;
+                    Statement::Step { name, op, prefix: false }
+                } else if let Some(Token::OpenParen) = self.current {
+                    Statement::Expression(self.parse_call(name)?)
+                } else if compound_operator(&self.current).is_none() {
+                    return Err(ParseError::new(format!("Unexpected identifier '{}'", name)).at(loc));
+                } else {
+                    self.parse_assignment(name)?
+                }
+            },
+            Some(Token::OpenBrace) => Statement::Block(self.parse_block()?),
+            Some(Token::KwIf) => self.parse_if()?,
+            Some(Token::KwMatch) => self.parse_match()?,
+            Some(Token::KwWhile) => {
+                self.consume()?;
+                let cond = self.parse_expression()?;
+                Statement::While { cond, body: self.parse_loop_body("the condition of 'tantque'")? }
+            },
+            Some(Token::KwDo) => {
+                self.consume()?;
+                let body = self.parse_loop_body("'fais'")?;
+                match &self.current {
+                    Some(Token::KwWhile) => self.consume()?,
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected 'tantque' after the block of 'fais'".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected 'tantque' after the block of 'fais', found {}", token))),
+                }
+                Statement::DoWhile { body, cond: self.parse_expression()? }
+            },
+            Some(Token::KwRepeat) => {
+                self.consume()?;
+                let count = self.parse_expression()?;
+                Statement::Repeat { count, body: self.parse_loop_body("the count of 'boucle'")? }
+            },
+            Some(Token::KwFunction) => self.parse_function()?,
+            Some(Token::KwReturn) => {
+                self.consume()?;
+                Statement::Return(self.parse_expression()?)
+            },
+            Some(token @ (Token::KwBreak | Token::KwContinue)) => {
+                if self.loop_depth == 0 {
+                    return Err(ParseError::new(format!("'{}' can only be used inside a loop", token.keyword_name().unwrap_or_default())));
+                }
+                let statement = if let Token::KwBreak = token { Statement::Break } else { Statement::Continue };
+                self.consume()?;
+                statement
+            },
+            _ => Statement::Expression(self.parse_expression()?),
+        };
+
+        match &self.current {
+            Some(Token::EndOfStatement) => self.consume()?,
+            // The last statement of the file may leave out its `;`.
+            Some(Token::Eof) | None => {}
+            Some(token) => return Err(ParseError::new(format!("Unexpected end of statement (; required), found {}", token))),
         }
-        left
+        Ok(statement)
     }
 
-    fn exponent_expression(&mut self) -> Expression {
-        let mut left = self.parse_literal();
-        while let Some(token) = &self.current {
-           match token {
-               Token::Exponent => {
-                   self.consume();
-                   left = Expression::Binary {
-                       op: BinaryExpressionType::Exponent,
-                       left: Box::new(left),
-                       right: Box::new(self.exponent_expression())
-                   };
-               },
-               _ => break,
-           };
+    /// Parses `{ statement; ... }`, the current token being the opening brace.
+    fn parse_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.consume()?;
+        let mut statements = Vec::new();
+        loop {
+            match &self.current {
+                Some(Token::CloseBrace) => {
+                    // Comments before the `}` stay in the block.
+                    self.push_pending_comments(&mut statements);
+                    self.consume()?;
+                    return Ok(statements);
+                }
+                Some(Token::Eof) | None => return Err(ParseError::new(format!("Unterminated block: expected '}}' after {} statement(s)", statements.len()))),
+                // An empty statement, as in `;;`.
+                Some(Token::EndOfStatement) => self.consume()?,
+                _ => statements.push(self.parse_statement()?),
+            }
         }
-        left
     }
 
-    fn parse_literal(&mut self) -> Expression {
-        match self.current.take() {
-            Some(Token::Number(n)) => {
-                self.consume();
-                Expression::Number(n)
-            }
-            Some(Token::OpenParen) => {
-                self.consume();
-                let expr = self.parse_expression();
-                if let Some(Token::CloseParen) = self.current.take() {
-                    self.consume();
-                    expr
+    /// Parses `si cond { ... } sinon { ... }`, the current token being `si`.
+    /// `sinon si` chains into a nested if in the else branch.
+    fn parse_if(&mut self) -> Result<Statement, ParseError> {
+        self.consume()?;
+        let cond = self.parse_expression()?;
+        let then_block = self.parse_braced_block("the condition of 'si'")?;
+        let else_block = match self.current {
+            Some(Token::KwElse) => {
+                self.consume()?;
+                if let Some(Token::KwIf) = self.current {
+                    Some(vec![self.parse_if()?])
                 } else {
-                    panic!("Expected ')' at the end");
+                    Some(self.parse_braced_block("'sinon'")?)
                 }
-            },
-            Some(Token::Identifier(id)) => {
-                self.consume();
-                Expression::Identifier(id)
             }
-            other => {
-                println!("Unexpected token: {:?}", other);
-                panic!("Expected a number");
+            _ => None,
+        };
+        Ok(Statement::If { cond, then_block, else_block })
+    }
+
+    /// Parses `selon value { pattern: { ... } autre: { ... } }`, the current
+    /// token being `selon`.
+    fn parse_match(&mut self) -> Result<Statement, ParseError> {
+        self.consume()?;
+        let subject = self.parse_expression()?;
+        match &self.current {
+            Some(Token::OpenBrace) => self.consume()?,
+            Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected '{' after the value of 'selon'".to_string())),
+            Some(token) => return Err(ParseError::new(format!("Expected '{{' after the value of 'selon', found {}", token))),
+        }
+        let mut arms = Vec::new();
+        let mut default = None;
+        loop {
+            match &self.current {
+                Some(Token::CloseBrace) => {
+                    self.consume()?;
+                    return Ok(Statement::Match { subject, arms, default });
+                }
+                Some(Token::Eof) | None => return Err(ParseError::new("Unterminated 'selon': expected '}' after its arms".to_string())),
+                _ if default.is_some() => return Err(ParseError::new("'autre' must be the last arm of 'selon'".to_string())),
+                Some(Token::KwDefault) => {
+                    self.consume()?;
+                    self.parse_arm_colon()?;
+                    default = Some(self.parse_braced_block("'autre:'")?);
+                }
+                _ => {
+                    let pattern = self.parse_expression()?;
+                    self.parse_arm_colon()?;
+                    arms.push((pattern, self.parse_braced_block("the ':' of a 'selon' arm")?));
+                }
             }
         }
     }
-}
-
-#[derive(Debug)]
-pub struct ExecuteError(String);
 
-impl Display for ExecuteError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "[EXECUTION] Error : {}", self.0)
+    fn parse_arm_colon(&mut self) -> Result<(), ParseError> {
+        match &self.current {
+            Some(Token::Colon) => self.consume(),
+            Some(Token::Eof) | None => Err(ParseError::new("Unexpected end of file, expected ':' after the value of an arm".to_string())),
+            Some(token) => Err(ParseError::new(format!("Expected ':' after the value of an arm, found {}", token))),
+        }
     }
-}
 
-impl Error for ExecuteError {}
+    /// Parses `fonction name(a, b) { ... }`, the current token being `fonction`.
+    fn parse_function(&mut self) -> Result<Statement, ParseError> {
+        self.consume()?;
+        let name = match &self.current {
+            Some(Token::Identifier(name)) => name.clone(),
+            Some(token) if token.keyword_name().is_some() => return Err(reserved_keyword(token)),
+            Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a function name".to_string())),
+            Some(token) => return Err(ParseError::new(format!("Expected a function name, found {}", token))),
+        };
+        self.consume()?;
+        match &self.current {
+            Some(Token::OpenParen) => self.consume()?,
+            Some(Token::Eof) | None => return Err(ParseError::new(format!("Unexpected end of file, expected '(' after 'fonction {}'", name))),
+            Some(token) => return Err(ParseError::new(format!("Expected '(' after 'fonction {}', found {}", name, token))),
+        }
 
-impl Expression {
-    pub fn evaluate(&self, variables: &HashMap<String, f64>) -> Result<f64, ExecuteError> {
-        match self {
-            Expression::Identifier(id) => {
-                if let Some(value) = variables.get(id) {
-                    Ok(*value)
+        let mut params = Vec::new();
+        if let Some(Token::CloseParen) = self.current {
+            self.consume()?;
+        } else {
+            loop {
+                let param = match &self.current {
+                    Some(Token::Identifier(param)) => param.clone(),
+                    Some(token) if token.keyword_name().is_some() => return Err(reserved_keyword(token)),
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a parameter name".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected a parameter name, found {}", token))),
+                };
+                if params.iter().any(|other: &Param| other.name == param) {
+                    return Err(ParseError::new(format!("Parameter '{}' of '{}' is declared twice", param, name)));
+                }
+                self.consume()?;
+                let default = if let Some(Token::Assign) = self.current {
+                    self.consume()?;
+                    Some(self.parse_expression()?)
+                } else if params.iter().any(|param: &Param| param.default.is_some()) {
+                    return Err(ParseError::new(format!("Parameter '{}' of '{}' needs a default value, as it follows one that has one", param, name)));
                 } else {
-                    Err(ExecuteError(format!("use of undefined variable {}", id)))
+                    None
+                };
+                params.push(Param { name: param, default });
+                match &self.current {
+                    Some(Token::Comma) => self.consume()?,
+                    Some(Token::CloseParen) => {
+                        self.consume()?;
+                        break;
+                    }
+                    Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected ')' after the parameters".to_string())),
+                    Some(token) => return Err(ParseError::new(format!("Expected ',' or ')' in the parameters of '{}', found {}", name, token))),
                 }
-            },
-            Expression::Number(n) => Ok(*n),
-            Expression::Binary { op, left, right} => {
-                match op {
-                    BinaryExpressionType::Sum => Ok(left.evaluate(variables)? + right.evaluate(variables)?),
-                    BinaryExpressionType::Product => Ok(left.evaluate(variables)? * right.evaluate(variables)?),
-                    BinaryExpressionType::Division => Ok(left.evaluate(variables)? / right.evaluate(variables)?),
-                    BinaryExpressionType::Minus => Ok(left.evaluate(variables)? - right.evaluate(variables)?),
-                    BinaryExpressionType::Exponent => Ok(left.evaluate(variables)?.powf(right.evaluate(variables)?)),
-                    BinaryExpressionType::BytesLeft => Ok((left.evaluate(variables)?.trunc() as u64).checked_shl(right.evaluate(variables)? as u32).unwrap_or(0) as f64),
-                    BinaryExpressionType::BytesRight => Ok((left.evaluate(variables)?.trunc() as u64).checked_shr(right.evaluate(variables)? as u32).unwrap_or(0) as f64),
+            }
+        }
+
+        // A loop around the definition does not enclose the body.
+        let enclosing_loops = std::mem::take(&mut self.loop_depth);
+        let body = self.parse_braced_block(&format!("the parameters of '{}'", name));
+        self.loop_depth = enclosing_loops;
+        let body = body?;
+        Ok(Statement::FunctionDef(Rc::new(Function { name, params, body })))
+    }
+
+    /// Parses the comma-separated values printed by `zipette` or `lsd`.
+    fn parse_values(&mut self) -> Result<Vec<Expression>, ParseError> {
+        let mut values = vec![self.parse_expression()?];
+        while let Some(Token::Comma) = self.current {
+            self.consume()?;
+            match &self.current {
+                Some(Token::EndOfStatement) => return Err(ParseError::new("Expected a value after ',', found ';'".to_string())),
+                _ => values.push(self.parse_expression()?),
+            }
+        }
+        Ok(values)
+    }
+
+    /// Parses a call, the current token being the `(` following the function
+    /// name.
+    fn parse_call(&mut self, name: String) -> Result<Expression, ParseError> {
+        let args = self.parse_call_args(&name)?;
+        Ok(Expression::Call { name, args })
+    }
+
+    /// Parses `(arg, ...)` after `name`, the current token being the `(`.
+    fn parse_call_args(&mut self, name: &str) -> Result<Vec<Expression>, ParseError> {
+        self.consume()?;
+        let mut args = Vec::new();
+        if let Some(Token::CloseParen) = self.current {
+            self.consume()?;
+            return Ok(args);
+        }
+        loop {
+            args.push(self.parse_expression()?);
+            match &self.current {
+                Some(Token::Comma) => self.consume()?,
+                Some(Token::CloseParen) => {
+                    self.consume()?;
+                    return Ok(args);
                 }
+                Some(Token::Eof) | None => return Err(ParseError::new(format!("Unexpected end of file, expected ')' to close the call to '{}'", name))),
+                Some(token) => return Err(ParseError::new(format!("Expected ',' or ')' in the arguments of '{}', found {}", name, token))),
             }
         }
     }
-}
 
-impl Statement {
-    pub fn execute(self, variables: &mut HashMap<String, f64>) -> Result<(), ExecuteError> {
-        match self {
-            Statement::Expression(expr) => expr.evaluate(variables).map(|_| ())?,
-            Statement::Print(expr) => println!("{}", expr.evaluate(variables)?),
-            Statement::Assignment(lhs, rhs) => {
-                variables.insert(lhs, rhs.evaluate(variables)?);
-            }
-            Statement::PrintColored(color, expr) => {
-                let value = format!("{}", expr.evaluate(variables)?);
-
-                match color {
-                    Colored::Red => println!("{}", value.red()),
-                    Colored::Blue => println!("{}", value.blue()),
-                    Colored::Yellow => println!("{}", value.yellow()),
-                    Colored::Green => println!("{}", value.green()),
-                    Colored::Purple => println!("{}", value.purple()),
-                    Colored::Cyan => println!("{}", value.cyan()),
-                    Colored::Orange => println!("{}", value.custom_color((255,127,0))),
-                    Colored::White => println!("{}", value.white()),
-                    Colored::Brown => println!("{}", value.custom_color((165,42,42))),
-                    Colored::Pink => println!("{}", value.custom_color((255,20,147))),
-                    Colored::MultiColor => {
-                        value.split("")
-                            .for_each(|x| {
-                                match Colored::random() {
-                                    Colored::Red => print!("{}", x.red()),
-                                    Colored::Blue => print!("{}", x.blue()),
-                                    Colored::Yellow => print!("{}", x.yellow()),
-                                    Colored::Green => print!("{}", x.green()),
-                                    Colored::Purple => print!("{}", x.purple()),
-                                    Colored::Cyan => print!("{}", x.cyan()),
-                                    Colored::Orange => print!("{}", x.custom_color((255,127,0))),
-                                    Colored::White => print!("{}", x.white()),
-                                    Colored::Brown => print!("{}", x.custom_color((165,42,42))),
-                                    Colored::Pink => print!("{}", x.custom_color((255,20,147))),
-                                    _ => unreachable!()
-                                }
-                            });
-                        println!();
-                    }
+    /// Parses the body of a loop, where `casse` and `continue` are allowed.
+    fn parse_loop_body(&mut self, what: &str) -> Result<Vec<Statement>, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_braced_block(what);
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parses a block that must follow `what`, reporting a missing `{`.
+    fn parse_braced_block(&mut self, what: &str) -> Result<Vec<Statement>, ParseError> {
+        match &self.current {
+            Some(Token::OpenBrace) => self.parse_block(),
+            Some(Token::Eof) | None => Err(ParseError::new(format!("Unexpected end of file, expected '{{' after {}", what))),
+            Some(token) => Err(ParseError::new(format!("Expected '{{' after {}, found {}", what, token))),
+        }
+    }
+
+    fn take_current(&mut self) -> Option<Token> {
+        self.current.take()
+    }
+
+    fn at_end(&self) -> bool {
+        matches!(self.current, Some(Token::Eof) | None)
+    }
+
+    /// The token after the current one, without consuming anything. Comments
+    /// are skipped like `consume` does, and keyword case is not normalized, so
+    /// this is meant for punctuation.
+    fn peek(&mut self) -> Option<&Token> {
+        let mut ahead = 0;
+        while let Some(Token::Comment { .. }) = self.tokens.peek_nth(ahead).and_then(ParserInput::token) {
+            ahead += 1;
+        }
+        self.tokens.peek_nth(ahead).and_then(ParserInput::token)
+    }
+
+    fn consume(&mut self) -> Result<(), ParseError> {
+        let (token, loc) = loop {
+            let Some((token, loc)) = self.tokens.next().map(ParserInput::into_token) else {
+                self.current = None;
+                return Ok(());
+            };
+            match token {
+                Ok(Token::Comment { text, own_line }) => self.comments.push((text, own_line)),
+                Ok(token) => break (token, loc),
+                // The bad token stays in place, for error recovery to skip it.
+                Err(err) => {
+                    self.current = Some(Token::Invalid);
+                    self.current_loc = loc.clone();
+                    return Err(ParseError::from(err).at(loc));
                 }
             }
         };
+        match token {
+            Token::OpenBrace => self.open_braces += 1,
+            Token::CloseBrace => self.open_braces = self.open_braces.saturating_sub(1),
+            _ => {}
+        }
+        self.current = Some(token);
+        self.current_loc = loc;
+        if self.ignore_keyword_case
+            && let Some(Token::Identifier(name)) = &self.current
+            && let Some(keyword) = lexer::keyword(&name.to_lowercase()) {
+            self.current = Some(keyword);
+        }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Parses what follows the variable name of an assignment: either a plain
+    /// value or a compound operator such as `+=` and its value.
+    fn parse_assignment(&mut self, name: String) -> Result<Statement, ParseError> {
+        if let Some(Token::Colon) = self.current {
+            self.consume()?;
+            let value_type = match &self.current {
+                Some(Token::Identifier(type_name)) => ValueType::from_name(type_name)
+                    .ok_or_else(|| ParseError::new(format!("Unknown type '{}' (expected nombre)", type_name)))?,
+                Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected a type name".to_string())),
+                Some(token) => return Err(ParseError::new(format!("Expected a type name, found {}", token))),
+            };
+            self.consume()?;
+            return Ok(Statement::TypedAssignment(name, value_type, self.parse_expression()?));
+        }
+
+        match compound_operator(&self.current) {
+            Some(op) => {
+                self.consume()?;
+                Ok(Statement::CompoundAssignment(name, op, self.parse_expression()?))
+            }
+            None => Ok(Statement::Assignment(name, self.parse_expression()?)),
+        }
+    }
+
+    pub fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.conditional_expression()
+    }
+
+    /// `cond ? then : otherwise`, right-associative so `a ? b : c ? d : e` nests in the else branch.
+    fn conditional_expression(&mut self) -> Result<Expression, ParseError> {
+        let cond = self.or_expression()?;
+        if !matches!(self.current, Some(Token::Question)) {
+            return Ok(cond);
+        }
+        self.consume()?;
+        let then = self.conditional_expression()?;
+        match &self.current {
+            Some(Token::Colon) => self.consume()?,
+            Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected ':' in conditional expression".to_string())),
+            Some(token) => return Err(ParseError::new(format!("Expected ':' in conditional expression, found {}", token))),
+        }
+        Ok(Expression::Conditional {
+            cond: Box::new(cond),
+            then: Box::new(then),
+            otherwise: Box::new(self.conditional_expression()?)
+        })
+    }
+
+    /// `||` and `&&` are the loosest binary operators, `&&` binding tighter.
+    fn or_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.and_expression()?;
+        while let Some(Token::Or) = &self.current {
+            self.consume()?;
+            left = Expression::Logical {
+                op: LogicalOp::Or,
+                left: Box::new(left),
+                right: Box::new(self.and_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    fn and_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.bit_or_expression()?;
+        while let Some(Token::And) = &self.current {
+            self.consume()?;
+            left = Expression::Logical {
+                op: LogicalOp::And,
+                left: Box::new(left),
+                right: Box::new(self.bit_or_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    fn bit_or_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.bit_xor_expression()?;
+        while let Some(Token::BitOr) = &self.current {
+            self.consume()?;
+            left = Expression::Binary {
+                op: BinaryExpressionType::BitOr,
+                left: Box::new(left),
+                right: Box::new(self.bit_xor_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    fn bit_xor_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.bit_and_expression()?;
+        while let Some(Token::BitXor) = &self.current {
+            self.consume()?;
+            left = Expression::Binary {
+                op: BinaryExpressionType::BitXor,
+                left: Box::new(left),
+                right: Box::new(self.bit_and_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    fn bit_and_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.comparison_expression()?;
+        while let Some(Token::BitAnd) = &self.current {
+            self.consume()?;
+            left = Expression::Binary {
+                op: BinaryExpressionType::BitAnd,
+                left: Box::new(left),
+                right: Box::new(self.comparison_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    /// Comparisons sit below shifts. They don't chain: `1 < 2 < 3` is an
+    /// error rather than the surprising `(1 < 2) < 3`.
+    fn comparison_expression(&mut self) -> Result<Expression, ParseError> {
+        let left = self.shift_expression()?;
+        let Some(op) = comparison_operator(&self.current) else {
+            return Ok(left);
+        };
+        self.consume()?;
+        let right = self.shift_expression()?;
+        if let Some(token) = &self.current
+            && comparison_operator(&self.current).is_some() {
+            return Err(ParseError::new(format!("Comparisons cannot be chained, found {} after a comparison (use parentheses)", token)));
+        }
+        Ok(Expression::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right)
+        })
+    }
+
+    /// Shifts bind looser than `+` and `*`, like in C and Rust: `1 << 2 + 3`
+    /// is `1 << (2 + 3)`. (They used to share the level of `*`.)
+    fn shift_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.term_expression()?;
+        while let Some(token) = &self.current {
+            let op = match token {
+                Token::BytesLeft => BinaryExpressionType::BytesLeft,
+                Token::BytesRight => BinaryExpressionType::BytesRight,
+                _ => break,
+            };
+
+            self.consume()?;
+            left = Expression::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(self.term_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    fn term_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.factor_expression()?;
+        while let Some(token) = &self.current {
+            let op = match token {
+                Token::Plus => BinaryExpressionType::Sum,
+                Token::Minus => BinaryExpressionType::Minus,
+                // `a --b` is `a - (-b)` and `a ++b` is `a + (+b)`: keep the
+                // second sign for the operand.
+                Token::Decrement | Token::Increment => {
+                    let (op, sign) = match token {
+                        Token::Decrement => (BinaryExpressionType::Minus, Token::Minus),
+                        _ => (BinaryExpressionType::Sum, Token::Plus),
+                    };
+                    self.current = Some(sign);
+                    left = Expression::Binary {
+                        op,
+                        left: Box::new(left),
+                        right: Box::new(self.factor_expression()?)
+                    };
+                    continue;
+                }
+                _ => break,
+            };
+
+            self.consume()?;
+            left = Expression::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(self.factor_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    fn factor_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.unary_expression()?;
+        while let Some(token) = &self.current {
+            let op = match token {
+                Token::Product => BinaryExpressionType::Product,
+                Token::Division => BinaryExpressionType::Division,
+                Token::Modulo => BinaryExpressionType::Modulo,
+                _ => break,
+            };
+
+            self.consume()?;
+            left = Expression::Binary {
+                op,
+                left: Box::new(left),
+                right: Box::new(self.unary_expression()?)
+            };
+        }
+        Ok(left)
+    }
+
+    /// Unary minus binds looser than `**`, as in Python: `-2 ** 2` is `-(2 ** 2)`.
+    /// Inside an expression `--x` is lexed as one token but means `-(-x)`.
+    /// Unary plus (and `++`) leaves its operand unchanged.
+    fn unary_expression(&mut self) -> Result<Expression, ParseError> {
+        let negations = match self.current {
+            Some(Token::Minus) => 1,
+            Some(Token::Decrement) => 2,
+            Some(Token::Plus | Token::Increment) => 0,
+            _ => return self.exponent_expression(),
+        };
+        self.consume()?;
+        let mut expr = self.unary_expression()?;
+        for _ in 0..negations {
+            expr = Expression::Unary { op: UnaryOp::Neg, expr: Box::new(expr) };
+        }
+        Ok(expr)
+    }
+
+    fn exponent_expression(&mut self) -> Result<Expression, ParseError> {
+        let mut left = self.parse_literal()?;
+        while let Some(token) = &self.current {
+           match token {
+               Token::Exponent => {
+                   self.consume()?;
+                   left = Expression::Binary {
+                       op: BinaryExpressionType::Exponent,
+                       left: Box::new(left),
+                       right: Box::new(self.unary_expression()?)
+                   };
+               },
+               _ => break,
+           };
+        }
+        Ok(left)
+    }
+
+    /// Parses a primary expression followed by any number of `[index]`.
+    fn parse_literal(&mut self) -> Result<Expression, ParseError> {
+        let mut expr = self.parse_primary()?;
+        while let Some(Token::OpenBracket) = self.current {
+            self.consume()?;
+            let index = self.parse_expression()?;
+            match &self.current {
+                Some(Token::CloseBracket) => {}
+                Some(token) => return Err(ParseError::new(format!("Expected ']' to close the index, found {}", token))),
+                None => return Err(ParseError::new("Unexpected end of file, expected ']'".to_string())),
+            }
+            self.consume()?;
+            expr = Expression::Index {
+                target: Box::new(expr),
+                index: Box::new(index)
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        match self.take_current() {
+            Some(Token::Number(n)) => {
+                self.consume()?;
+                Ok(Expression::Number(n))
+            }
+            Some(Token::Str(text)) => {
+                self.consume()?;
+                Ok(Expression::Str(text))
+            }
+            Some(Token::InterpolatedStr(parts)) => {
+                let start = self.current_loc.clone();
+                self.consume()?;
+                parse_interpolation(parts, start)
+            }
+            Some(Token::OpenParen) => {
+                self.consume()?;
+                if let Some(Token::CloseParen) = self.current {
+                    return Err(ParseError::new("Empty parentheses '()', expected an expression inside".to_string()));
+                }
+                let expr = self.parse_expression()?;
+                match &self.current {
+                    Some(Token::CloseParen) => {
+                        self.consume()?;
+                        Ok(expr)
+                    }
+                    Some(Token::Eof) | None => Err(ParseError::new("Unexpected end of file, expected ')' to close '('".to_string())),
+                    Some(token) => Err(ParseError::new(format!("Expected ')' to close the parenthesis, found {}", token))),
+                }
+            },
+            Some(Token::Identifier(id)) => {
+                self.consume()?;
+                if let Some(Token::OpenParen) = self.current {
+                    return self.parse_call(id);
+                }
+                Ok(Expression::Identifier(id))
+            }
+            Some(Token::OpenBracket) => {
+                self.consume()?;
+                let mut elements = Vec::new();
+                if let Some(Token::CloseBracket) = self.current {
+                    self.consume()?;
+                    return Ok(Expression::Array(elements));
+                }
+                loop {
+                    if let Some(Token::CloseBracket) = self.current {
+                        return Err(ParseError::new("Expected an element after ',' in the array, found ']'".to_string()));
+                    }
+                    elements.push(self.parse_expression()?);
+                    match &self.current {
+                        Some(Token::Comma) => self.consume()?,
+                        Some(Token::CloseBracket) => {
+                            self.consume()?;
+                            return Ok(Expression::Array(elements));
+                        }
+                        Some(Token::Eof) | None => return Err(ParseError::new("Unexpected end of file, expected ']' to close the array".to_string())),
+                        Some(token) => return Err(ParseError::new(format!("Expected ',' or ']' in the array, found {}", token))),
+                    }
+                }
+            }
+            Some(Token::BitNot) => {
+                self.consume()?;
+                Ok(Expression::Unary {
+                    op: UnaryOp::BitNot,
+                    expr: Box::new(self.parse_literal()?)
+                })
+            }
+            Some(Token::KwRandom) => {
+                self.consume()?;
+                if !matches!(self.current, Some(Token::OpenParen)) {
+                    return Ok(Expression::Random(None));
+                }
+                let args = self.parse_call_args("hasard")?;
+                if args.is_empty() {
+                    return Ok(Expression::Random(None));
+                }
+                let range = <[Expression; 2]>::try_from(args)
+                    .map_err(|args| ParseError::new(format!("'hasard' expects no arguments or a minimum and a maximum, got {} argument(s)", args.len())))?;
+                Ok(Expression::Random(Some(Box::new(range))))
+            }
+            Some(Token::KwClock) => {
+                self.consume()?;
+                Ok(Expression::Clock)
+            }
+            // Booleans are numbers for now: `vrai` is 1 and `faux` is 0.
+            Some(Token::KwTrue) => {
+                self.consume()?;
+                Ok(Expression::Number(1.0))
+            }
+            Some(Token::KwFalse) => {
+                self.consume()?;
+                Ok(Expression::Number(0.0))
+            }
+            // The token is put back, for error recovery to see where the
+            // statement ends.
+            token => {
+                let err = match &token {
+                    Some(token) if token.keyword_name().is_some() => reserved_keyword(token),
+                    Some(Token::Eof) | None => ParseError::new("Unexpected end of file, expected an expression".to_string()),
+                    Some(Token::CloseBracket) => ParseError::new("Unexpected ']' without a matching '['".to_string()),
+                    Some(other) => ParseError::new(format!("Expected an expression, found {}", other)),
+                };
+                self.current = token;
+                Err(err)
+            }
+        }
+    }
+}
+
+/// Parses the `${...}` of a string literal found at `start`. When the
+/// position is known, each embedded expression is lexed from where it sits
+/// in the file so that its errors point into the string; otherwise they
+/// point at the string itself.
+fn parse_interpolation(parts: Vec<StrPart>, start: Option<Location>) -> Result<Expression, ParseError> {
+    // Line and column just past the opening quote.
+    let mut position = start.as_ref().map(|loc| (loc.line, loc.start_column + 1));
+    let advance = |position: &mut Option<(usize, usize)>, text: &str| {
+        if let Some((line, column)) = position {
+            for c in text.chars() {
+                if c == '\n' {
+                    *line += 1;
+                    *column = 1;
+                } else {
+                    *column += 1;
+                }
+            }
+        }
+    };
+    let mut result = Vec::new();
+    for part in parts {
+        match part {
+            StrPart::Text(text) => {
+                advance(&mut position, &text);
+                result.push(StringPart::Text(text));
+            }
+            StrPart::Expr(source) => {
+                advance(&mut position, "${");
+                let expr = match position {
+                    Some((line, column)) => parse_embedded(Parser::new(Lexer::resume(&source, line, column).localized()), &source),
+                    None => parse_embedded(Parser::new(Lexer::new(&source)), &source),
+                }.map_err(|err| err.at(start.clone()))?;
+                advance(&mut position, &source);
+                advance(&mut position, "}");
+                result.push(StringPart::Expression(expr));
+            }
+        }
+    }
+    Ok(Expression::Interpolation(result))
+}
+
+/// Parses the `source` of one `${...}` with `parser`.
+fn parse_embedded<I: Iterator<Item: ParserInput>>(mut parser: Parser<I>, source: &str) -> Result<Expression, ParseError> {
+    parser.consume()?;
+    let expression = parser.parse_expression().map_err(|err| err.at(parser.current_loc.clone()))?;
+    match &parser.current {
+        Some(Token::Eof) | None => Ok(expression),
+        Some(token) => Err(ParseError::new(format!("Unexpected {} in '${{{}}}'", token, source)).at(parser.current_loc.clone())),
+    }
+}
+
+fn reserved_keyword(token: &Token) -> ParseError {
+    ParseError::new(format!("'{}' is a reserved keyword", token.keyword_name().unwrap_or_default()))
+}
+
+fn step_operator(token: &Option<Token>) -> BinaryExpressionType {
+    match token {
+        Some(Token::Decrement) => BinaryExpressionType::Minus,
+        _ => BinaryExpressionType::Sum,
+    }
+}
+
+/// The value of the variable `name`, which must exist.
+fn variable(name: &str, env: &Environment) -> Result<Value, ExecuteError> {
+    env.get(name).ok_or_else(|| ExecuteError(format!("use of undefined variable {}", name)))
+}
+
+fn comparison_operator(token: &Option<Token>) -> Option<BinaryExpressionType> {
+    match token {
+        Some(Token::Equal) => Some(BinaryExpressionType::Eq),
+        Some(Token::NotEqual) => Some(BinaryExpressionType::Ne),
+        Some(Token::Less) => Some(BinaryExpressionType::Lt),
+        Some(Token::Greater) => Some(BinaryExpressionType::Gt),
+        Some(Token::LessEqual) => Some(BinaryExpressionType::Le),
+        Some(Token::GreaterEqual) => Some(BinaryExpressionType::Ge),
+        _ => None,
+    }
+}
+
+fn truth(value: bool) -> Value {
+    Value::Number(if value { 1.0 } else { 0.0 })
+}
+
+fn compound_operator(token: &Option<Token>) -> Option<BinaryExpressionType> {
+    match token {
+        Some(Token::PlusAssign) => Some(BinaryExpressionType::Sum),
+        Some(Token::MinusAssign) => Some(BinaryExpressionType::Minus),
+        Some(Token::StarAssign) => Some(BinaryExpressionType::Product),
+        Some(Token::SlashAssign) => Some(BinaryExpressionType::Division),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct ExecuteError(pub(crate) String);
+
+impl Display for ExecuteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[EXECUTION] Error : {}", self.0)
+    }
+}
+
+impl Error for ExecuteError {}
+
+impl From<io::Error> for ExecuteError {
+    fn from(err: io::Error) -> Self {
+        ExecuteError(format!("failed to write output: {}", err))
+    }
+}
+
+impl BinaryExpressionType {
+    /// The operator as written in the source.
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryExpressionType::Sum => "+",
+            BinaryExpressionType::Product => "*",
+            BinaryExpressionType::Division => "/",
+            BinaryExpressionType::Modulo => "%",
+            BinaryExpressionType::Minus => "-",
+            BinaryExpressionType::Exponent => "**",
+            BinaryExpressionType::BytesLeft => "<<",
+            BinaryExpressionType::BytesRight => ">>",
+            BinaryExpressionType::BitAnd => "&",
+            BinaryExpressionType::BitOr => "|",
+            BinaryExpressionType::BitXor => "xor",
+            BinaryExpressionType::Eq => "==",
+            BinaryExpressionType::Ne => "!=",
+            BinaryExpressionType::Lt => "<",
+            BinaryExpressionType::Gt => ">",
+            BinaryExpressionType::Le => "<=",
+            BinaryExpressionType::Ge => ">=",
+        }
+    }
+
+    /// Binding strength, following the parser: from 4 for `|` to 12 for `**`.
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryExpressionType::BitOr => 4,
+            BinaryExpressionType::BitXor => 5,
+            BinaryExpressionType::BitAnd => 6,
+            BinaryExpressionType::Eq | BinaryExpressionType::Ne | BinaryExpressionType::Lt
+            | BinaryExpressionType::Gt | BinaryExpressionType::Le | BinaryExpressionType::Ge => 7,
+            BinaryExpressionType::BytesLeft | BinaryExpressionType::BytesRight => 8,
+            BinaryExpressionType::Sum | BinaryExpressionType::Minus => 9,
+            BinaryExpressionType::Product | BinaryExpressionType::Division | BinaryExpressionType::Modulo => 10,
+            BinaryExpressionType::Exponent => 12,
+        }
+    }
+
+    pub(crate) fn apply(&self, left: Value, right: Value) -> Result<Value, ExecuteError> {
+        match (self, left, right) {
+            // `+` concatenates as soon as one side is a string.
+            (BinaryExpressionType::Sum, Value::Str(left), right) => Ok(Value::Str(left + &right.to_string())),
+            (BinaryExpressionType::Sum, left, Value::Str(right)) => Ok(Value::Str(left.to_string() + &right)),
+            // Values of different types are unequal rather than an error.
+            (BinaryExpressionType::Eq, left, right) => Ok(truth(left == right)),
+            (BinaryExpressionType::Ne, left, right) => Ok(truth(left != right)),
+            (op, Value::Number(left), Value::Number(right)) => Ok(op.apply_numbers(left, right)),
+            (op, left, right) => Err(ExecuteError(format!("cannot apply '{}' to {} and {}", op.symbol(), left.type_name(), right.type_name()))),
+        }
+    }
+
+    fn apply_numbers(&self, left: f64, right: f64) -> Value {
+        match self {
+            BinaryExpressionType::Sum => Value::Number(left + right),
+            BinaryExpressionType::Product => Value::Number(left * right),
+            BinaryExpressionType::Division => Value::Number(left / right),
+            // Follows IEEE like the other arithmetic operators: `x % 0` is NaN.
+            BinaryExpressionType::Modulo => Value::Number(left % right),
+            BinaryExpressionType::Minus => Value::Number(left - right),
+            BinaryExpressionType::Exponent => Value::Number(left.powf(right)),
+            BinaryExpressionType::BytesLeft => Value::Number((left.trunc() as u64).checked_shl(right as u32).unwrap_or(0) as f64),
+            BinaryExpressionType::BytesRight => Value::Number((left.trunc() as u64).checked_shr(right as u32).unwrap_or(0) as f64),
+            BinaryExpressionType::BitAnd => Value::Number((left.trunc() as u64 & right.trunc() as u64) as f64),
+            BinaryExpressionType::BitOr => Value::Number((left.trunc() as u64 | right.trunc() as u64) as f64),
+            BinaryExpressionType::BitXor => Value::Number((left.trunc() as u64 ^ right.trunc() as u64) as f64),
+            // Comparisons yield 1 for true and 0 for false.
+            BinaryExpressionType::Eq => truth(left == right),
+            BinaryExpressionType::Ne => truth(left != right),
+            BinaryExpressionType::Lt => truth(left < right),
+            BinaryExpressionType::Gt => truth(left > right),
+            BinaryExpressionType::Le => truth(left <= right),
+            BinaryExpressionType::Ge => truth(left >= right),
+        }
+    }
+}
+
+impl UnaryOp {
+    pub(crate) fn apply(&self, value: Value) -> Result<Value, ExecuteError> {
+        match (self, value) {
+            (UnaryOp::BitNot, Value::Number(n)) => Ok(Value::Number(!(n.trunc() as u64) as f64)),
+            (UnaryOp::Neg, Value::Number(n)) => Ok(Value::Number(-n)),
+            (op, value) => {
+                let symbol = match op { UnaryOp::BitNot => "~", UnaryOp::Neg => "-" };
+                Err(ExecuteError(format!("cannot apply '{}' to {}", symbol, value.type_name())))
+            }
+        }
+    }
+}
+
+impl Expression {
+    /// Binding strength, following the parser: 1 for a conditional up to 13
+    /// for literals, names, calls and indexing.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expression::Conditional { .. } => 1,
+            Expression::Logical { op: LogicalOp::Or, .. } => 2,
+            Expression::Logical { op: LogicalOp::And, .. } => 3,
+            Expression::Binary { op, .. } => op.precedence(),
+            Expression::Unary { op: UnaryOp::Neg, .. } => 11,
+            _ => 13,
+        }
+    }
+
+    /// Writes the expression, in parentheses if it binds looser than `min`.
+    fn fmt_operand(&self, f: &mut Formatter<'_>, min: u8) -> std::fmt::Result {
+        if self.precedence() < min {
+            write!(f, "({})", self)
+        } else {
+            write!(f, "{}", self)
+        }
+    }
+
+    fn is_literal(&self) -> bool {
+        matches!(self, Expression::Number(_) | Expression::Str(_))
+    }
+
+    pub fn evaluate(&self, env: &mut Environment) -> Result<Value, ExecuteError> {
+        match self {
+            Expression::Identifier(id) => variable(id, env),
+            Expression::Number(n) => Ok(Value::Number(*n)),
+            Expression::Str(text) => Ok(Value::Str(text.clone())),
+            Expression::Binary { op, left, right} => {
+                let left = left.evaluate(env)?;
+                op.apply(left, right.evaluate(env)?)
+            }
+            Expression::Unary { op, expr } => op.apply(expr.evaluate(env)?),
+            Expression::Array(elements) => {
+                elements.iter().map(|element| element.evaluate(env)).collect::<Result<_, _>>().map(Value::Array)
+            }
+            Expression::Index { target, index } => {
+                let target = target.evaluate(env)?;
+                let index = match index.evaluate(env)? {
+                    Value::Number(index) => index,
+                    value => return Err(ExecuteError(format!("an index must be a nombre, got {}", value.type_name()))),
+                };
+                let Value::Array(mut elements) = target else {
+                    return Err(ExecuteError(format!("cannot index a {}", target.type_name())));
+                };
+                if index < 0.0 || index.fract() != 0.0 || index >= elements.len() as f64 {
+                    return Err(ExecuteError(format!("index {} is out of range for an array of length {}", index, elements.len())));
+                }
+                Ok(elements.swap_remove(index as usize))
+            }
+            // The right operand is only evaluated when it decides the result.
+            Expression::Logical { op, left, right } => {
+                let left = left.evaluate(env)?.is_truthy();
+                match op {
+                    LogicalOp::And if !left => Ok(truth(false)),
+                    LogicalOp::Or if left => Ok(truth(true)),
+                    _ => Ok(truth(right.evaluate(env)?.is_truthy())),
+                }
+            }
+            // Only the selected branch is evaluated.
+            Expression::Conditional { cond, then, otherwise } => {
+                if cond.evaluate(env)?.is_truthy() {
+                    then.evaluate(env)
+                } else {
+                    otherwise.evaluate(env)
+                }
+            }
+            Expression::Interpolation(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    match part {
+                        StringPart::Text(part) => text += part,
+                        StringPart::Expression(expr) => text += &expr.evaluate(env)?.to_string(),
+                    }
+                }
+                Ok(Value::Str(text))
+            }
+            Expression::Clock => Ok(Value::Number((env.clock)())),
+            Expression::Random(range) => {
+                let (min, max) = match range.as_deref() {
+                    None => (0.0, 1.0),
+                    Some(range) => {
+                        let mut bounds = [0.0; 2];
+                        for (bound, expr) in bounds.iter_mut().zip(range) {
+                            match expr.evaluate(env)? {
+                                Value::Number(n) => *bound = n,
+                                value => return Err(ExecuteError(format!("'hasard' expects nombre bounds, got {}", value.type_name()))),
+                            }
+                        }
+                        (bounds[0], bounds[1])
+                    }
+                };
+                if min > max {
+                    return Err(ExecuteError(format!("'hasard' needs a minimum no greater than its maximum, got {} and {}", min, max)));
+                }
+                Ok(Value::Number(min + (max - min) * env.rng.random::<f64>()))
+            }
+            Expression::Call { name, args } => {
+                let Some(function) = env.function(name) else {
+                    let Some(builtin) = builtins::lookup(name) else {
+                        return Err(ExecuteError(format!("call to undefined function '{}'", name)));
+                    };
+                    return builtin(args.iter().map(|arg| arg.evaluate(env)).collect::<Result<_, _>>()?);
+                };
+                let required = function.required_params();
+                if args.len() < required || args.len() > function.params.len() {
+                    let expected = if required == function.params.len() {
+                        required.to_string()
+                    } else {
+                        format!("{} to {}", required, function.params.len())
+                    };
+                    return Err(ExecuteError(format!("function '{}' expects {} argument(s), got {}", name, expected, args.len())));
+                }
+                if env.call_depth() >= MAX_CALL_DEPTH {
+                    return Err(ExecuteError(format!("call to '{}' stopped after {} nested calls, the maximum allowed", name, MAX_CALL_DEPTH)));
+                }
+                // Arguments are evaluated in the caller's scope.
+                let values = args.iter().map(|arg| arg.evaluate(env)).collect::<Result<Vec<_>, _>>()?;
+                let arguments = function.params.iter().map(|param| param.name.clone()).zip(values);
+                let call = env.call(arguments, |env| {
+                    // Defaults are evaluated in the new scope, where the
+                    // parameters before them are already bound.
+                    for param in &function.params[args.len()..] {
+                        let value = param.default.as_ref().expect("checked against required_params").evaluate(env)?;
+                        env.define(&param.name, value);
+                    }
+                    execute_all(&function.body, env)
+                });
+                match call? {
+                    ControlFlow::Return(value) => Ok(value),
+                    // An expression cannot unwind, so the exit travels as an
+                    // error that `Interpreter::run` recognizes.
+                    ControlFlow::Exit(code) => {
+                        env.request_exit(code);
+                        Err(ExecuteError(format!("exiting with code {}", code)))
+                    }
+                    // The parser only accepts `casse` and `continue` inside a loop.
+                    ControlFlow::Normal | ControlFlow::Break | ControlFlow::Continue => Ok(Value::Number(0.0)),
+                }
+            }
+        }
+    }
+}
+
+/// Renders the expression as source code, with only the parentheses needed
+/// to parse it back the same way.
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Str(text) => write!(f, "\"{}\"", text),
+            Expression::Identifier(name) => write!(f, "{}", name),
+            Expression::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Expression::Interpolation(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match part {
+                        StringPart::Text(text) => write!(f, "{}", text)?,
+                        StringPart::Expression(expr) => write!(f, "${{{}}}", expr)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+            Expression::Random(None) => write!(f, "hasard"),
+            Expression::Clock => write!(f, "horloge"),
+            Expression::Random(Some(range)) => write!(f, "hasard({}, {})", range[0], range[1]),
+            Expression::Call { name, args } => {
+                write!(f, "{}(", name)?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Index { target, index } => {
+                target.fmt_operand(f, 13)?;
+                write!(f, "[{}]", index)
+            }
+            Expression::Unary { op: UnaryOp::Neg, expr } => {
+                write!(f, "-")?;
+                match **expr {
+                    // `--x` would read as a decrement.
+                    Expression::Unary { op: UnaryOp::Neg, .. } => write!(f, "({})", expr),
+                    Expression::Number(n) if n.is_sign_negative() => write!(f, "({})", expr),
+                    _ => expr.fmt_operand(f, 11),
+                }
+            }
+            Expression::Unary { op: UnaryOp::BitNot, expr } => {
+                write!(f, "~")?;
+                expr.fmt_operand(f, 13)
+            }
+            Expression::Binary { op, left, right } => {
+                let precedence = op.precedence();
+                let (left_min, right_min) = match op {
+                    // `**` is right-associative and takes a unary right operand.
+                    BinaryExpressionType::Exponent => (13, 11),
+                    // Comparisons do not chain.
+                    _ if precedence == 7 => (8, 8),
+                    _ => (precedence, precedence + 1),
+                };
+                left.fmt_operand(f, left_min)?;
+                write!(f, " {} ", op.symbol())?;
+                right.fmt_operand(f, right_min)
+            }
+            Expression::Logical { op, left, right } => {
+                let precedence = self.precedence();
+                left.fmt_operand(f, precedence)?;
+                write!(f, " {} ", match op { LogicalOp::And => "&&", LogicalOp::Or => "||" })?;
+                right.fmt_operand(f, precedence + 1)
+            }
+            Expression::Conditional { cond, then, otherwise } => {
+                cond.fmt_operand(f, 2)?;
+                write!(f, " ? {} : {}", then, otherwise)
+            }
+        }
+    }
+}
+
+impl Display for BinaryExpressionType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl Display for Colored {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Colored::Custom(red, green, blue) => write!(f, "rgb({}, {}, {})", red, green, blue),
+            color => {
+                let name = Colored::NAMES.iter().find(|name| Colored::from_name(name).as_ref() == Some(color));
+                write!(f, "{}", name.expect("every named color is in Colored::NAMES"))
+            }
+        }
+    }
+}
+
+impl Display for ValueType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueType::Number => write!(f, "nombre"),
+        }
+    }
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.default {
+            Some(default) => write!(f, "{} = {}", self.name, default),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+/// Renders the statement as source, `;` included, so that parsing the output
+/// gives the same statement back. Blocks span several lines, indented by four
+/// spaces per level.
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_terminated(f, 0)
+    }
+}
+
+/// Formats a whole program the canonical way: one statement per line,
+/// blocks indented by four spaces, single spaces around operators, numbers
+/// written with a `.` and only the parentheses that are needed. Comments,
+/// a `#!` line, color names and the `+=` and `++` shorthands are kept as
+/// written. Formatting the result again gives it back unchanged.
+pub fn format_source(source: &str) -> Result<String, ParseError> {
+    let program = Parser::new(Lexer::new(source).with_comments()).parse()?;
+    let shebang = source.trim_start_matches('\u{feff}').lines().next().filter(|line| line.starts_with("#!"));
+    let mut formatted: String = shebang.map(|line| format!("{}\n", line.trim_end())).unwrap_or_default();
+    formatted.extend(program.iter().map(|statement| format!("{}\n", statement)));
+    Ok(formatted)
+}
+
+impl Statement {
+    /// Writes the statement and its `;`, then its trailing comment if it has
+    /// one.
+    fn fmt_terminated(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
+        self.fmt_indented(f, indent)?;
+        if let Statement::Comments(_) = self {
+            return Ok(());
+        }
+        write!(f, ";")?;
+        if let Statement::Commented { trailing: Some(comment), .. } = self {
+            write!(f, " {}", comment)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_indented(&self, f: &mut Formatter<'_>, indent: usize) -> std::fmt::Result {
+        match self {
+            Statement::Commented { comments, statement, .. } => {
+                for comment in comments {
+                    writeln!(f, "{}", comment)?;
+                    write!(f, "{:width$}", "", width = indent * 4)?;
+                }
+                statement.fmt_indented(f, indent)
+            }
+            Statement::Comments(comments) => {
+                let separator = format!("\n{:width$}", "", width = indent * 4);
+                write!(f, "{}", comments.join(&separator))
+            }
+            Statement::Expression(expr) => write!(f, "{}", expr),
+            Statement::Print(values) if values.is_empty() => write!(f, "zipette"),
+            Statement::Print(values) => write!(f, "zipette {}", Values(values)),
+            Statement::Write(values) => write!(f, "ecrit {}", Values(values)),
+            Statement::Input { name, prompt: None } => write!(f, "demande {}", name),
+            Statement::Input { name, prompt: Some(prompt) } => write!(f, "demande {} {}", name, prompt),
+            Statement::PrintColored(_, name, values) => write!(f, "lsd {} {}", name, Values(values)),
+            Statement::PrintColoredDyn(color, values) => {
+                write!(f, "lsd ({}) ", color)?;
+                // `lsd (c) [1];` would index the color instead.
+                match values.split_first() {
+                    Some((first, rest)) if first.to_string().starts_with('[') => {
+                        write!(f, "({})", first)?;
+                        rest.iter().try_for_each(|value| write!(f, ", {}", value))
+                    }
+                    _ => write!(f, "{}", Values(values)),
+                }
+            }
+            Statement::PrintColoredRgb(components, values) => {
+                let [red, green, blue] = &**components;
+                write!(f, "lsd rgb({}, {}, {}) {}", red, green, blue, Values(values))
+            }
+            Statement::Assignment(name, value) => write!(f, "vicer {} {}", name, value),
+            Statement::CompoundAssignment(name, op, value) => write!(f, "{} {}= {}", name, op.symbol(), value),
+            Statement::Step { name, op, prefix: true } => write!(f, "{}{}{}", op.symbol(), op.symbol(), name),
+            Statement::Step { name, op, prefix: false } => write!(f, "{}{}{}", name, op.symbol(), op.symbol()),
+            Statement::Constant(name, value) => write!(f, "fixe {} {}", name, value),
+            Statement::Forget(name) => write!(f, "oublie {}", name),
+            Statement::Import(file) => write!(f, "importe \"{}\"", file),
+            Statement::TypedAssignment(name, value_type, value) => write!(f, "vicer {}: {} {}", name, value_type, value),
+            Statement::Assert(cond) => write!(f, "verifie {}", cond),
+            Statement::Exit(None) => write!(f, "quitte"),
+            Statement::Exit(Some(code)) => write!(f, "quitte {}", code),
+            Statement::Sleep(duration) => write!(f, "attend {}", duration),
+            Statement::Block(statements) => fmt_block(f, statements, indent),
+            Statement::If { cond, then_block, else_block } => {
+                write!(f, "si {} ", cond)?;
+                fmt_block(f, then_block, indent)?;
+                match else_block.as_deref() {
+                    None => Ok(()),
+                    Some([nested @ Statement::If { .. }]) => {
+                        write!(f, " sinon ")?;
+                        nested.fmt_indented(f, indent)
+                    }
+                    Some(else_block) => {
+                        write!(f, " sinon ")?;
+                        fmt_block(f, else_block, indent)
+                    }
+                }
+            }
+            Statement::While { cond, body } => {
+                write!(f, "tantque {} ", cond)?;
+                fmt_block(f, body, indent)
+            }
+            Statement::DoWhile { body, cond } => {
+                write!(f, "fais ")?;
+                fmt_block(f, body, indent)?;
+                write!(f, " tantque {}", cond)
+            }
+            Statement::Repeat { count, body } => {
+                write!(f, "boucle {} ", count)?;
+                fmt_block(f, body, indent)
+            }
+            Statement::Match { subject, arms, default } => {
+                writeln!(f, "selon {} {{", subject)?;
+                let arms = arms.iter().map(|(pattern, block)| (pattern.to_string(), block));
+                for (pattern, block) in arms.chain(default.iter().map(|block| ("autre".to_string(), block))) {
+                    write!(f, "{:width$}{}: ", "", pattern, width = (indent + 1) * 4)?;
+                    fmt_block(f, block, indent + 1)?;
+                    writeln!(f)?;
+                }
+                write!(f, "{:width$}}}", "", width = indent * 4)
+            }
+            Statement::FunctionDef(function) => {
+                write!(f, "fonction {}(", function.name)?;
+                for (i, param) in function.params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") ")?;
+                fmt_block(f, &function.body, indent)
+            }
+            Statement::Return(value) => write!(f, "renvoie {}", value),
+            Statement::Break => write!(f, "casse"),
+            Statement::Continue => write!(f, "continue"),
+        }
+    }
+}
+
+/// The values of `zipette`, `ecrit` or `lsd`, separated by commas.
+struct Values<'a>(&'a [Expression]);
+
+impl Display for Values<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, value) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", value)?;
+        }
+        Ok(())
+    }
+}
+
+fn fmt_block(f: &mut Formatter<'_>, statements: &[Statement], indent: usize) -> std::fmt::Result {
+    writeln!(f, "{{")?;
+    for statement in statements {
+        write!(f, "{:width$}", "", width = (indent + 1) * 4)?;
+        statement.fmt_terminated(f, indent + 1)?;
+        writeln!(f)?;
+    }
+    write!(f, "{:width$}}}", "", width = indent * 4)
+}
+
+/// Variable holding the current iteration of a `boucle`, starting at 0.
+const LOOP_INDEX: &str = "indice";
+
+/// Runs `body` as long as `cond` holds, checking it before each iteration,
+/// or only from the second one on for `fais`.
+fn run_while(cond: &Expression, body: &[Statement], check_first: bool, env: &mut Environment) -> Result<ControlFlow, ExecuteError> {
+    let mut iterations = 0;
+    loop {
+        if (check_first || iterations > 0) && !cond.evaluate(env)?.is_truthy() {
+            break;
+        }
+        iterations += 1;
+        if let Some(max) = env.max_iterations
+            && iterations > max {
+            return Err(ExecuteError(format!("loop stopped after {} iterations, the maximum allowed", max)));
+        }
+        match execute_all(body, env)? {
+            ControlFlow::Normal | ControlFlow::Continue => {}
+            ControlFlow::Break => break,
+            flow @ (ControlFlow::Return(_) | ControlFlow::Exit(_)) => return Ok(flow),
+        }
+    }
+    Ok(ControlFlow::Normal)
+}
+
+/// Runs `statements` in order, stopping at the first one that unwinds.
+pub fn execute_all(statements: &[Statement], env: &mut Environment) -> Result<ControlFlow, ExecuteError> {
+    for statement in statements {
+        match statement.execute(env)? {
+            ControlFlow::Normal => {}
+            flow => return Ok(flow),
+        }
+    }
+    Ok(ControlFlow::Normal)
+}
+
+impl Statement {
+    pub fn execute(&self, env: &mut Environment) -> Result<ControlFlow, ExecuteError> {
+        match self {
+            Statement::Expression(expr) => expr.evaluate(env).map(|_| ())?,
+            Statement::Print(values) => {
+                let line = format_values(values, env)?;
+                writeln!(env.output, "{}", line)?;
+            }
+            Statement::Write(values) => {
+                let line = format_values(values, env)?;
+                write!(env.output, "{}", line)?;
+                // Nothing ends the line, so make sure the text shows up now.
+                env.output.flush()?;
+            }
+            Statement::Input { name, prompt } => {
+                if let Some(prompt) = prompt {
+                    let prompt = prompt.evaluate(env)?;
+                    write!(env.output, "{}", prompt)?;
+                    env.output.flush()?;
+                }
+                let mut line = String::new();
+                let read = env.input.read_line(&mut line)
+                    .map_err(|err| ExecuteError(format!("failed to read input: {}", err)))?;
+                if read == 0 {
+                    return Err(ExecuteError(format!("no input left to read into '{}'", name)));
+                }
+                let Some(value) = lexer::number(&line) else {
+                    return Err(ExecuteError(format!("expected a number for '{}', got '{}'", name, line.trim_end_matches(['\r', '\n']))));
+                };
+                env.assign(name, Value::Number(value))?;
+            }
+            Statement::Assignment(lhs, rhs) => {
+                let value = rhs.evaluate(env)?;
+                env.assign(lhs, value)?;
+            }
+            Statement::CompoundAssignment(lhs, op, rhs) => {
+                let value = variable(lhs, env)?;
+                let value = op.apply(value, rhs.evaluate(env)?)?;
+                env.assign(lhs, value)?;
+            }
+            Statement::Step { name, op, .. } => {
+                let value = op.apply(variable(name, env)?, Value::Number(1.0))?;
+                env.assign(name, value)?;
+            }
+            Statement::Assert(cond) => {
+                // Operands are evaluated once, then shown if the check fails.
+                let mut operands = Vec::new();
+                let mut show = |expr: &Expression, value: &Value| {
+                    if !expr.is_literal() {
+                        operands.push(format!("{} = {}", expr, value));
+                    }
+                };
+                let passed = match cond {
+                    Expression::Binary { op, left, right } => {
+                        let (left_value, right_value) = (left.evaluate(env)?, right.evaluate(env)?);
+                        show(left, &left_value);
+                        show(right, &right_value);
+                        op.apply(left_value, right_value)?.is_truthy()
+                    }
+                    Expression::Identifier(_) => {
+                        let value = cond.evaluate(env)?;
+                        show(cond, &value);
+                        value.is_truthy()
+                    }
+                    _ => cond.evaluate(env)?.is_truthy(),
+                };
+                if !passed {
+                    let details = if operands.is_empty() { String::new() } else { format!(" ({})", operands.join(", ")) };
+                    return Err(ExecuteError(format!("assertion failed: {}{}", cond, details)));
+                }
+            }
+            Statement::Constant(name, rhs) => {
+                let value = rhs.evaluate(env)?;
+                env.define_constant(name, value)?;
+            }
+            Statement::Forget(name) => env.forget(name)?,
+            Statement::Import(file) => {
+                let path = env.import_path(file);
+                let path = path.canonicalize()
+                    .map_err(|err| ExecuteError(format!("cannot import '{}': {}", path.display(), err)))?;
+                let flow = env.import(path.clone(), |env| {
+                    let source = fs::read_to_string(&path)
+                        .map_err(|err| ExecuteError(format!("cannot import '{}': {}", path.display(), err)))?;
+                    let program = Parser::new(Lexer::new(&source).with_tab_width(env.tab_width).localized()).parse()
+                        .map_err(|err| ExecuteError(format!("in '{}': {}", path.display(), err)))?;
+                    execute_all(&program, env)
+                })?;
+                // A top-level `renvoie` only ends the imported file.
+                if let ControlFlow::Exit(code) = flow {
+                    return Ok(ControlFlow::Exit(code));
+                }
+            }
+            Statement::TypedAssignment(lhs, value_type, rhs) => {
+                let value = rhs.evaluate(env)?;
+                match (value_type, &value) {
+                    (ValueType::Number, Value::Number(_)) => {}
+                    (ValueType::Number, value) => {
+                        return Err(ExecuteError(format!("'{}' is declared as nombre but was given {}", lhs, value.type_name())));
+                    }
+                }
+                env.assign(lhs, value)?;
+            }
+            Statement::Block(statements) => {
+                env.push_scope();
+                let result = execute_all(statements, env);
+                env.pop_scope();
+                return result;
+            }
+            Statement::If { cond, then_block, else_block } => {
+                let block = if cond.evaluate(env)?.is_truthy() { Some(then_block) } else { else_block.as_ref() };
+                if let Some(block) = block {
+                    return execute_all(block, env);
+                }
+            }
+            Statement::While { cond, body } => return run_while(cond, body, true, env),
+            Statement::DoWhile { body, cond } => return run_while(cond, body, false, env),
+            Statement::Match { subject, arms, default } => {
+                let subject = subject.evaluate(env)?;
+                for (pattern, block) in arms {
+                    if BinaryExpressionType::Eq.apply(subject.clone(), pattern.evaluate(env)?)?.is_truthy() {
+                        return execute_all(block, env);
+                    }
+                }
+                if let Some(block) = default {
+                    return execute_all(block, env);
+                }
+            }
+            Statement::Repeat { count, body } => {
+                let count = match count.evaluate(env)? {
+                    Value::Number(count) => count,
+                    value => return Err(ExecuteError(format!("'boucle' needs a number of iterations, got {}", value.type_name()))),
+                };
+                if count.is_nan() || count < 0.0 {
+                    return Err(ExecuteError(format!("'boucle' needs a positive number of iterations, got {}", count)));
+                }
+                let count = count.trunc() as u64;
+                if let Some(max) = env.max_iterations
+                    && count > max as u64 {
+                    return Err(ExecuteError(format!("loop stopped after {} iterations, the maximum allowed", max)));
+                }
+                // An enclosing loop's `indice` is shadowed, then restored.
+                let outer_index = env.get(LOOP_INDEX);
+                let mut result = Ok(ControlFlow::Normal);
+                for index in 0..count {
+                    if let Err(err) = env.assign(LOOP_INDEX, Value::Number(index as f64)) {
+                        result = Err(err);
+                        break;
+                    }
+                    match execute_all(body, env) {
+                        Ok(ControlFlow::Normal | ControlFlow::Continue) => {}
+                        Ok(ControlFlow::Break) => break,
+                        flow => {
+                            result = flow;
+                            break;
+                        }
+                    }
+                }
+                match outer_index {
+                    Some(value) => env.assign(LOOP_INDEX, value)?,
+                    None => env.remove(LOOP_INDEX),
+                }
+                return result;
+            }
+            Statement::FunctionDef(function) => env.define_function(Rc::clone(function)),
+            Statement::Return(expr) => return Ok(ControlFlow::Return(expr.evaluate(env)?)),
+            Statement::Exit(code) => {
+                let code = match code.as_ref().map(|code| code.evaluate(env)).transpose()? {
+                    None => 0.0,
+                    Some(Value::Number(code)) => code,
+                    Some(value) => return Err(ExecuteError(format!("'quitte' needs a number as exit code, got {}", value.type_name()))),
+                };
+                // The system only keeps the low byte of the code.
+                if !(0.0..256.0).contains(&code) {
+                    return Err(ExecuteError(format!("'quitte' needs an exit code between 0 and 255, got {}", code)));
+                }
+                return Ok(ControlFlow::Exit(code.trunc() as i32));
+            }
+            Statement::Sleep(duration) => {
+                let duration = match duration.evaluate(env)? {
+                    Value::Number(ms) if ms >= 0.0 => Duration::try_from_secs_f64(ms / 1000.0).unwrap_or(Duration::MAX),
+                    Value::Number(ms) => return Err(ExecuteError(format!("'attend' needs a positive number of milliseconds, got {}", ms))),
+                    value => return Err(ExecuteError(format!("'attend' needs a number of milliseconds, got {}", value.type_name()))),
+                };
+                (env.sleep)(duration);
+            }
+            Statement::Break => return Ok(ControlFlow::Break),
+            Statement::Continue => return Ok(ControlFlow::Continue),
+            Statement::Commented { statement, .. } => return statement.execute(env),
+            Statement::Comments(_) => {}
+            Statement::PrintColored(color, _, values) => {
+                let line = format_values(values, env)?;
+                print_colored(env, color, &line)?;
+            }
+            Statement::PrintColoredDyn(color, values) => {
+                let color = match color.evaluate(env)? {
+                    Value::Number(index) if index.fract() == 0.0 && (0.0..=10.0).contains(&index) => Colored::from_index(index as usize),
+                    value => return Err(ExecuteError(format!("a color must be a number from 0 to 10, got {}", value))),
+                };
+                let line = format_values(values, env)?;
+                print_colored(env, &color, &line)?;
+            }
+            Statement::PrintColoredRgb(components, values) => {
+                let mut rgb = [0; 3];
+                for (component, expr) in rgb.iter_mut().zip(components.iter()) {
+                    *component = match expr.evaluate(env)? {
+                        Value::Number(n) if n.fract() == 0.0 && (0.0..=255.0).contains(&n) => n as u8,
+                        value => return Err(ExecuteError(format!("an rgb component must be a number from 0 to 255, got {}", value))),
+                    };
+                }
+                let line = format_values(values, env)?;
+                print_colored(env, &Colored::Custom(rgb[0], rgb[1], rgb[2]), &line)?;
+            }
+        };
+        Ok(ControlFlow::Normal)
+    }
+}
+
+/// Evaluates `values` from left to right, joined by a space.
+fn format_values(values: &[Expression], env: &mut Environment) -> Result<String, ExecuteError> {
+    let values = values.iter().map(|value| value.evaluate(env).map(|value| value.to_string())).collect::<Result<Vec<_>, _>>()?;
+    Ok(values.join(" "))
+}
+
+/// Prints `value` on its own line in `color`.
+fn print_colored(env: &mut Environment, color: &Colored, value: &str) -> io::Result<()> {
+    match color {
+        Colored::Red => writeln!(env.output, "{}", value.red())?,
+        Colored::Blue => writeln!(env.output, "{}", value.blue())?,
+        Colored::Yellow => writeln!(env.output, "{}", value.yellow())?,
+        Colored::Green => writeln!(env.output, "{}", value.green())?,
+        Colored::Purple => writeln!(env.output, "{}", value.purple())?,
+        Colored::Cyan => writeln!(env.output, "{}", value.cyan())?,
+        Colored::Orange => writeln!(env.output, "{}", value.custom_color((255,127,0)))?,
+        Colored::White => writeln!(env.output, "{}", value.white())?,
+        Colored::Brown => writeln!(env.output, "{}", value.custom_color((165,42,42)))?,
+        Colored::Pink => writeln!(env.output, "{}", value.custom_color((255,20,147)))?,
+        Colored::Custom(r, g, b) => writeln!(env.output, "{}", value.custom_color((*r, *g, *b)))?,
+        Colored::MultiColor => {
+            for x in value.split("") {
+                match Colored::random(&mut env.rng) {
+                    Colored::Red => write!(env.output, "{}", x.red())?,
+                    Colored::Blue => write!(env.output, "{}", x.blue())?,
+                    Colored::Yellow => write!(env.output, "{}", x.yellow())?,
+                    Colored::Green => write!(env.output, "{}", x.green())?,
+                    Colored::Purple => write!(env.output, "{}", x.purple())?,
+                    Colored::Cyan => write!(env.output, "{}", x.cyan())?,
+                    Colored::Orange => write!(env.output, "{}", x.custom_color((255,127,0)))?,
+                    Colored::White => write!(env.output, "{}", x.white())?,
+                    Colored::Brown => write!(env.output, "{}", x.custom_color((165,42,42)))?,
+                    Colored::Pink => write!(env.output, "{}", x.custom_color((255,20,147)))?,
+                    _ => unreachable!()
+                }
+            }
+            writeln!(env.output)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{Lexer, LocalizedToken};
+
+    fn parse(source: &str) -> Result<Vec<Statement>, ParseError> {
+        Parser::new(Lexer::new(source)).parse()
+    }
+
+    fn ast(source: &str) -> Vec<Statement> {
+        parse(source).unwrap()
+    }
+
+    fn parse_error(source: &str) -> String {
+        parse(source).unwrap_err().to_string()
+    }
+
+    /// The first error, its position included.
+    fn located_error(source: &str) -> String {
+        Parser::new(Lexer::new(source).localized()).parse().unwrap_err().to_string()
+    }
+
+    fn parse_all(source: &str) -> Result<Vec<Statement>, Vec<ParseError>> {
+        Parser::new(Lexer::new(source).localized()).parse_all()
+    }
+
+    /// The single value printed by a `zipette` statement.
+    fn printed(statement: &Statement) -> &Expression {
+        match statement {
+            Statement::Print(values) if values.len() == 1 => &values[0],
+            other => panic!("expected a print of one value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn comment_only_file_is_an_empty_program() {
+        let program = ast("# nothing to see\n# here");
+        assert!(program.is_empty());
+    }
+
+    #[test]
+    fn block_comment_in_expression_evaluates_around_it() {
+        let program = ast("zipette 1 + /* ignored */ 2;");
+        assert_eq!(printed(&program[0]).evaluate(&mut Environment::new(Box::new(io::sink()))).unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn scientific_notation_prints_as_plain_number() {
+        let program = ast("zipette 1e3;");
+        assert_eq!(printed(&program[0]).evaluate(&mut Environment::new(Box::new(io::sink()))).unwrap().to_string(), "1000");
+    }
+
+    #[test]
+    fn nested_blocks() {
+        let program = ast("{ vicer x 1; { zipette x; zipette 2; }; };");
+        assert_eq!(program.len(), 1);
+        match &program[0] {
+            Statement::Block(outer) => {
+                assert_eq!(outer.len(), 2);
+                assert!(matches!(&outer[1], Statement::Block(inner) if inner.len() == 2));
+            }
+            other => panic!("expected a block, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unterminated_block_reports_statements_read() {
+        assert_eq!(parse_error("{ zipette 1; zipette 2;"), "[PARSER] Error : Unterminated block: expected '}' after 2 statement(s)");
+    }
+
+    #[test]
+    fn index_expression() {
+        let program = ast("zipette t[1 + 2];");
+        match printed(&program[0]) {
+            Expression::Index { target, index } => {
+                assert!(matches!(target.as_ref(), Expression::Identifier(id) if id == "t"));
+                assert!(matches!(index.as_ref(), Expression::Binary { op: BinaryExpressionType::Sum, .. }));
+            }
+            other => panic!("expected an index expression, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unmatched_brackets_are_parse_errors() {
+        assert_eq!(parse_error("zipette t[1;"), "[PARSER] Error : Expected ']' to close the index, found ';'");
+        assert_eq!(parse_error("zipette [1;"), "[PARSER] Error : Expected ',' or ']' in the array, found ';'");
+        assert_eq!(parse_error("zipette [1, ];"), "[PARSER] Error : Expected an element after ',' in the array, found ']'");
+        assert_eq!(parse_error("zipette ];"), "[PARSER] Error : Unexpected ']' without a matching '['");
+        assert_eq!(parse_error("zipette t[1]];"), "[PARSER] Error : Unexpected end of statement (; required), found ']'");
+    }
+
+    #[test]
+    fn lexer_errors_become_parse_errors() {
+        assert_eq!(parse_error("1 @ 2;"), "[PARSER] Error : LexerError: Unexpected character '@'");
+        assert_eq!(parse_error("@"), "[PARSER] Error : LexerError: Unexpected character '@'");
+        // The lexer still knows where a malformed literal is.
+        assert_eq!(parse_error("vicer x 0b3;"), "[PARSER] Error at line 1, column 9: LexerError: Invalid digit '3' in number literal '0b3'");
+    }
+
+    #[test]
+    fn keywords_are_reserved() {
+        assert_eq!(parse_error("vicer zipette 3;"), "[PARSER] Error : 'zipette' is a reserved keyword");
+        assert_eq!(parse_error("vicer lsd 3;"), "[PARSER] Error : 'lsd' is a reserved keyword");
+        assert_eq!(parse_error("zipette vicer + 1;"), "[PARSER] Error : 'vicer' is a reserved keyword");
+    }
+
+    #[test]
+    fn keywords_must_match_exactly() {
+        assert_eq!(parse_error("zipettes 1;"), "[PARSER] Error : Unexpected identifier 'zipettes'");
+        let program = ast("vicer vicerx 1;");
+        assert!(matches!(&program[0], Statement::Assignment(name, _) if name == "vicerx"));
+    }
+
+    #[test]
+    fn empty_file_is_an_empty_program() {
+        assert!(ast("").is_empty());
+        assert!(ast("  \n ").is_empty());
+    }
+
+    #[test]
+    fn unexpected_end_of_file() {
+        assert_eq!(parse_error("zipette 1 +"), "[PARSER] Error : Unexpected end of file, expected an expression");
+        assert_eq!(parse_error("vicer x"), "[PARSER] Error : Unexpected end of file, expected an expression");
+        assert_eq!(parse_error("vicer"), "[PARSER] Error : Unexpected end of file, expected a variable name");
+    }
+
+    #[test]
+    fn collected_tokens_are_reused_for_parsing() {
+        let source = "vicer x 1; zipette x + 2;\n".repeat(5_000);
+        let tokens: Vec<LocalizedToken> = Lexer::new(&source).localized().collect();
+        let mut pulled = 0;
+        let program = Parser::new(tokens.iter().cloned().inspect(|_| pulled += 1)).parse_all().unwrap();
+        // The parser read every collected token once, and got the program it
+        // gets from the source.
+        assert_eq!(pulled, tokens.len());
+        assert_eq!(program.len(), 10_000);
+        assert_eq!(program, parse_all(&source).unwrap());
+
+        let source = "zipette 1;\nvicer x @;\nzipette (2;";
+        let from_tokens = Parser::new(Lexer::new(source).localized().collect::<Vec<_>>().into_iter()).parse_all().unwrap_err();
+        let from_source = parse_all(source).unwrap_err();
+        assert_eq!(from_tokens.iter().map(ToString::to_string).collect::<Vec<_>>(), from_source.iter().map(ToString::to_string).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn tokens_helper_matches_lexer_iterator() {
+        let source = "zipette 1 << 2; vicer y 0x1F;";
+        let collected = crate::lexer::tokens(source);
+        assert_eq!(collected, Lexer::new(source).map(Result::unwrap).collect::<Vec<_>>());
+        assert!(matches!(collected.last(), Some(Token::Eof)));
+    }
+
+    #[test]
+    fn typed_assignment() {
+        let program = ast("vicer x: nombre 5; vicer y 2;");
+        assert!(matches!(&program[0], Statement::TypedAssignment(name, ValueType::Number, Expression::Number(n)) if name == "x" && *n == 5.0));
+        assert!(matches!(&program[1], Statement::Assignment(name, _) if name == "y"));
+    }
+
+    #[test]
+    fn unknown_type_annotation_is_a_parse_error() {
+        assert_eq!(parse_error("vicer x: texte 5;"), "[PARSER] Error : Unknown type 'texte' (expected nombre)");
+        assert_eq!(parse_error("vicer x: 5;"), "[PARSER] Error : Expected a type name, found number 5");
+    }
+
+    #[test]
+    fn conditional_is_right_associative() {
+        let program = ast("zipette a ? b : c ? d : e;");
+        let Expression::Conditional { cond, then, otherwise } = printed(&program[0]) else {
+            panic!("expected a conditional, got {:?}", program[0]);
+        };
+        assert!(matches!(cond.as_ref(), Expression::Identifier(name) if name == "a"));
+        assert!(matches!(then.as_ref(), Expression::Identifier(name) if name == "b"));
+        assert!(matches!(otherwise.as_ref(), Expression::Conditional { .. }));
+    }
+
+    #[test]
+    fn conditional_binds_looser_than_arithmetic() {
+        let program = ast("1 + 1 ? 2 : 3 - 1;");
+        assert!(matches!(&program[0], Statement::Expression(Expression::Conditional { cond, otherwise, .. })
+            if matches!(cond.as_ref(), Expression::Binary { op: BinaryExpressionType::Sum, .. })
+                && matches!(otherwise.as_ref(), Expression::Binary { op: BinaryExpressionType::Minus, .. })));
+    }
+
+    #[test]
+    fn conditional_without_colon_is_a_parse_error() {
+        assert_eq!(parse_error("1 ? 2;"), "[PARSER] Error : Expected ':' in conditional expression, found ';'");
+    }
+
+    #[test]
+    fn increment_and_decrement_statements() {
+        let step = |op, prefix| Statement::Step { name: "i".to_string(), op, prefix };
+        assert_eq!(ast("i++; --i; ++i; i--;"), [
+            step(BinaryExpressionType::Sum, false),
+            step(BinaryExpressionType::Minus, true),
+            step(BinaryExpressionType::Sum, true),
+            step(BinaryExpressionType::Minus, false),
+        ]);
+
+        assert_eq!(parse_error("++5;"), "[PARSER] Error : Expected a variable name after '++' or '--', found number 5");
+    }
+
+    #[test]
+    fn subtraction_and_unary_minus() {
+        for source in ["zipette 3 -2;", "zipette 3 - 2;"] {
+            assert!(matches!(printed(&ast(source)[0]), Expression::Binary { op: BinaryExpressionType::Minus, .. }), "{}", source);
+        }
+        assert!(matches!(printed(&ast("zipette -2;")[0]), Expression::Unary { op: UnaryOp::Neg, expr }
+            if matches!(expr.as_ref(), Expression::Number(n) if *n == 2.0)));
+        assert!(matches!(printed(&ast("zipette -2 ** 2;")[0]), Expression::Unary { op: UnaryOp::Neg, expr }
+            if matches!(expr.as_ref(), Expression::Binary { op: BinaryExpressionType::Exponent, .. })));
+        assert!(matches!(printed(&ast("zipette (-2) ** 2;")[0]), Expression::Binary { op: BinaryExpressionType::Exponent, left, .. }
+            if matches!(left.as_ref(), Expression::Unary { op: UnaryOp::Neg, .. })));
+    }
+
+    #[test]
+    fn print_interpolated_string() {
+        let program = ast("zipette \"x = ${x * 2}\";");
+        assert!(matches!(printed(&program[0]), Expression::Interpolation(parts)
+            if matches!(parts.as_slice(), [StringPart::Text(text), StringPart::Expression(Expression::Binary { op: BinaryExpressionType::Product, .. })] if text == "x = ")));
+
+        assert_eq!(parse_error("zipette \"${1 2}\";"), "[PARSER] Error : Unexpected number 2 in '${1 2}'");
+    }
+
+    #[test]
+    fn keyword_case_is_only_ignored_in_lenient_mode() {
+        assert_eq!(parse_error("ZIPETTE 3;"), "[PARSER] Error : Unexpected identifier 'ZIPETTE'");
+
+        let program = Parser::new(Lexer::new("ZIPETTE 3; Vicer X 2; LSD Red X;")).ignore_keyword_case().parse().unwrap();
+        assert!(matches!(printed(&program[0]), Expression::Number(n) if *n == 3.0));
+        assert!(matches!(&program[1], Statement::Assignment(name, _) if name == "X"));
+        assert!(matches!(&program[2], Statement::PrintColored(Colored::Red, _, values)
+            if matches!(values.as_slice(), [Expression::Identifier(name)] if name == "X")));
+
+        assert!(parse_error("lsd Red 1;").starts_with("[PARSER] Error : Unrecognised color type 'Red' (expected one of red,"));
+    }
+
+    #[test]
+    fn malformed_expressions_are_parse_errors() {
+        assert_eq!(parse_error("ecrit ;"), "[PARSER] Error : Expected an expression, found ';'");
+        assert_eq!(parse_error("zipette (1+2;"), "[PARSER] Error : Expected ')' to close the parenthesis, found ';'");
+        assert_eq!(parse_error("zipette (1+2"), "[PARSER] Error : Unexpected end of file, expected ')' to close '('");
+        assert_eq!(parse_error("zipette +;"), "[PARSER] Error : Expected an expression, found ';'");
+        assert_eq!(parse_error("vicer x *;"), "[PARSER] Error : Expected an expression, found '*'");
+    }
+
+    #[test]
+    fn unary_minus_nesting() {
+        assert!(matches!(printed(&ast("zipette -(2 + 3);")[0]), Expression::Unary { op: UnaryOp::Neg, expr }
+            if matches!(expr.as_ref(), Expression::Binary { op: BinaryExpressionType::Sum, .. })));
+        assert!(matches!(printed(&ast("zipette --x;")[0]), Expression::Unary { op: UnaryOp::Neg, expr }
+            if matches!(expr.as_ref(), Expression::Unary { op: UnaryOp::Neg, expr } if matches!(expr.as_ref(), Expression::Identifier(_)))));
+        assert!(matches!(printed(&ast("zipette -x ** 2;")[0]), Expression::Unary { op: UnaryOp::Neg, expr }
+            if matches!(expr.as_ref(), Expression::Binary { op: BinaryExpressionType::Exponent, .. })));
+    }
+
+    #[test]
+    fn unary_plus_is_a_no_op() {
+        assert!(matches!(printed(&ast("zipette +5;")[0]), Expression::Number(n) if *n == 5.0));
+        assert!(matches!(&ast("vicer x +(2*3);")[0], Statement::Assignment(_, Expression::Binary { op: BinaryExpressionType::Product, .. })));
+        assert_eq!(parse_error("zipette + ;"), "[PARSER] Error : Expected an expression, found ';'");
+    }
+
+    #[test]
+    fn unbalanced_parentheses() {
+        assert_eq!(parse_error("zipette (1 + 2;"), "[PARSER] Error : Expected ')' to close the parenthesis, found ';'");
+        assert_eq!(parse_error("zipette ((1+2)"), "[PARSER] Error : Unexpected end of file, expected ')' to close '('");
+        assert_eq!(parse_error("zipette ();"), "[PARSER] Error : Empty parentheses '()', expected an expression inside");
+    }
+
+    #[test]
+    fn shifts_bind_looser_than_addition() {
+        assert!(matches!(printed(&ast("zipette 1 << 2 + 3;")[0]), Expression::Binary { op: BinaryExpressionType::BytesLeft, right, .. }
+            if matches!(right.as_ref(), Expression::Binary { op: BinaryExpressionType::Sum, .. })));
+        assert!(matches!(printed(&ast("zipette 2 + 1 << 3;")[0]), Expression::Binary { op: BinaryExpressionType::BytesLeft, left, .. }
+            if matches!(left.as_ref(), Expression::Binary { op: BinaryExpressionType::Sum, .. })));
+    }
+
+    #[test]
+    fn comparisons() {
+        assert!(matches!(printed(&ast("zipette 1 + 1 == 2;")[0]), Expression::Binary { op: BinaryExpressionType::Eq, left, .. }
+            if matches!(left.as_ref(), Expression::Binary { op: BinaryExpressionType::Sum, .. })));
+        assert!(matches!(printed(&ast("zipette 1 << 2 >= 4;")[0]), Expression::Binary { op: BinaryExpressionType::Ge, .. }));
+        assert!(matches!(printed(&ast("zipette (1 < 2) < 3;")[0]), Expression::Binary { op: BinaryExpressionType::Lt, .. }));
+        assert_eq!(
+            parse_error("zipette 1 < 2 < 3;"),
+            "[PARSER] Error : Comparisons cannot be chained, found '<' after a comparison (use parentheses)"
+        );
+    }
+
+    #[test]
+    fn logical_operators_precedence() {
+        assert!(matches!(printed(&ast("zipette a || b && c;")[0]), Expression::Logical { op: LogicalOp::Or, right, .. }
+            if matches!(right.as_ref(), Expression::Logical { op: LogicalOp::And, .. })));
+        assert!(matches!(printed(&ast("zipette a < 1 && b == 2;")[0]), Expression::Logical { op: LogicalOp::And, left, right }
+            if matches!(left.as_ref(), Expression::Binary { op: BinaryExpressionType::Lt, .. })
+                && matches!(right.as_ref(), Expression::Binary { op: BinaryExpressionType::Eq, .. })));
+        assert!(matches!(printed(&ast("zipette a && b ? 1 : 2;")[0]), Expression::Conditional { cond, .. }
+            if matches!(cond.as_ref(), Expression::Logical { .. })));
+    }
+
+    #[test]
+    fn boolean_literals() {
+        assert!(matches!(printed(&ast("zipette vrai;")[0]), Expression::Number(n) if *n == 1.0));
+        assert!(matches!(printed(&ast("zipette faux;")[0]), Expression::Number(n) if *n == 0.0));
+        assert_eq!(parse_error("vicer vrai 2;"), "[PARSER] Error : 'vrai' is a reserved keyword");
+    }
+
+    #[test]
+    fn if_statement() {
+        let program = ast("si x > 1 { zipette 1; } sinon si x { zipette 2; } sinon { zipette 3; }; si 1 { };");
+        assert!(matches!(&program[0], Statement::If { cond: Expression::Binary { op: BinaryExpressionType::Gt, .. }, then_block, else_block: Some(else_block) }
+            if then_block.len() == 1 && matches!(else_block.as_slice(), [Statement::If { else_block: Some(_), .. }])));
+        assert!(matches!(&program[1], Statement::If { then_block, else_block: None, .. } if then_block.is_empty()));
+
+        assert_eq!(parse_error("si 1 zipette 2;"), "[PARSER] Error : Expected '{' after the condition of 'si', found keyword 'zipette'");
+        assert_eq!(parse_error("si 1 { } sinon;"), "[PARSER] Error : Expected '{' after 'sinon', found ';'");
+        assert_eq!(parse_error("vicer si 1;"), "[PARSER] Error : 'si' is a reserved keyword");
+    }
+
+    #[test]
+    fn while_statement() {
+        let program = ast("tantque i < 3 { i++; };");
+        assert!(matches!(&program[0], Statement::While { cond: Expression::Binary { op: BinaryExpressionType::Lt, .. }, body } if body.len() == 1));
+        assert_eq!(parse_error("tantque 1;"), "[PARSER] Error : Expected '{' after the condition of 'tantque', found ';'");
+    }
+
+    #[test]
+    fn repeat_statement() {
+        assert!(matches!(&ast("boucle n * 2 { zipette indice; };")[0], Statement::Repeat { count: Expression::Binary { .. }, body } if body.len() == 1));
+        assert_eq!(parse_error("boucle 3 zipette 1;"), "[PARSER] Error : Expected '{' after the count of 'boucle', found keyword 'zipette'");
+    }
+
+    #[test]
+    fn function_definitions_and_calls() {
+        let program = ast("fonction aire(l, h) { renvoie l * h; }; zipette aire(3, 4) + 1; pause();");
+        assert!(matches!(&program[0], Statement::FunctionDef(function)
+            if function.name == "aire" && function.params.iter().map(|param| &param.name).eq(["l", "h"]) && matches!(function.body.as_slice(), [Statement::Return(Expression::Binary { .. })])));
+        assert!(matches!(printed(&program[1]), Expression::Binary { left, .. }
+            if matches!(left.as_ref(), Expression::Call { name, args } if name == "aire" && args.len() == 2)));
+        assert!(matches!(&program[2], Statement::Expression(Expression::Call { args, .. }) if args.is_empty()));
+
+        assert_eq!(parse_error("fonction (a) { };"), "[PARSER] Error : Expected a function name, found '('");
+        assert!(matches!(&ast("fonction aire(l, h = l * 2) { };")[0], Statement::FunctionDef(function)
+            if function.required_params() == 1 && matches!(function.params[1].default, Some(Expression::Binary { .. }))));
+        assert_eq!(parse_error("fonction f(a = 1, b) { };"),
+            "[PARSER] Error : Parameter 'b' of 'f' needs a default value, as it follows one that has one");
+        assert_eq!(parse_error("fonction f(a, a) { renvoie a; };"), "[PARSER] Error : Parameter 'a' of 'f' is declared twice");
+        assert_eq!(located_error("fonction f(a, b = 1, a = 2) { };"), "[PARSER] Error at line 1, column 22: Parameter 'a' of 'f' is declared twice");
+        assert_eq!(parse_error("fonction f(a b) { };"), "[PARSER] Error : Expected ',' or ')' in the parameters of 'f', found identifier 'b'");
+        assert_eq!(parse_error("fonction f(si) { };"), "[PARSER] Error : 'si' is a reserved keyword");
+        assert_eq!(parse_error("fonction f() renvoie 1;"), "[PARSER] Error : Expected '{' after the parameters of 'f', found keyword 'renvoie'");
+        assert_eq!(parse_error("zipette f(1, 2;"), "[PARSER] Error : Expected ',' or ')' in the arguments of 'f', found ';'");
+    }
+
+    #[test]
+    fn break_and_continue_only_parse_inside_loops() {
+        assert!(matches!(&ast("tantque 1 { si x { casse; }; continue; };")[0], Statement::While { body, .. }
+            if matches!(body.as_slice(), [Statement::If { .. }, Statement::Continue])));
+        assert!(matches!(&ast("boucle 2 { { casse; }; };")[0], Statement::Repeat { .. }));
+
+        assert_eq!(parse_error("casse;"), "[PARSER] Error : 'casse' can only be used inside a loop");
+        assert_eq!(parse_error("si 1 { continue; };"), "[PARSER] Error : 'continue' can only be used inside a loop");
+        assert_eq!(parse_error("boucle 2 { fonction f() { casse; }; };"), "[PARSER] Error : 'casse' can only be used inside a loop");
+        assert!(parse("boucle 2 { }; casse;").is_err());
+    }
+
+    #[test]
+    fn string_literals_are_expressions() {
+        let program = ast("zipette \"bonjour \" + nom; vicer s \"a\";");
+        assert!(matches!(printed(&program[0]), Expression::Binary { op: BinaryExpressionType::Sum, left, .. }
+            if matches!(left.as_ref(), Expression::Str(text) if text == "bonjour ")));
+        assert!(matches!(&program[1], Statement::Assignment(name, Expression::Str(text)) if name == "s" && text == "a"));
+    }
+
+    #[test]
+    fn array_literals_and_indexing() {
+        assert!(matches!(printed(&ast("zipette [1, [2, 3], []];")[0]), Expression::Array(elements)
+            if matches!(elements.as_slice(), [Expression::Number(_), Expression::Array(inner), Expression::Array(empty)] if inner.len() == 2 && empty.is_empty())));
+        assert!(matches!(printed(&ast("zipette t[0][1];")[0]), Expression::Index { target, .. }
+            if matches!(target.as_ref(), Expression::Index { .. })));
+        assert!(matches!(printed(&ast("zipette [4, 5][1];")[0]), Expression::Index { target, .. }
+            if matches!(target.as_ref(), Expression::Array(_))));
+    }
+
+    #[test]
+    fn lsd_with_a_computed_color() {
+        let program = ast("lsd (c) x; lsd couleur(c) x + 1; lsd 2 x; lsd red x;");
+        assert!(matches!(&program[0], Statement::PrintColoredDyn(Expression::Identifier(c), values)
+            if c == "c" && matches!(values.as_slice(), [Expression::Identifier(x)] if x == "x")));
+        assert!(matches!(&program[1], Statement::PrintColoredDyn(Expression::Call { .. }, values) if matches!(values.as_slice(), [Expression::Binary { .. }])));
+        assert!(matches!(&program[2], Statement::PrintColoredDyn(Expression::Number(_), values) if matches!(values.as_slice(), [Expression::Identifier(_)])));
+        assert!(matches!(&program[3], Statement::PrintColored(Colored::Red, _, values) if matches!(values.as_slice(), [Expression::Identifier(_)])));
+
+        // The color ends where its primary expression does.
+        let neg = |expr| Expression::Unary { op: UnaryOp::Neg, expr: Box::new(expr) };
+        assert_eq!(ast("lsd (c) -5; lsd couleur(c) -x, 1; lsd t[0] -1; lsd 2 - 1;"), [
+            Statement::PrintColoredDyn(var("c"), vec![neg(num(5.0))]),
+            Statement::PrintColoredDyn(Expression::Call { name: "couleur".to_string(), args: vec![var("c")] }, vec![neg(var("x")), num(1.0)]),
+            Statement::PrintColoredDyn(Expression::Index { target: Box::new(var("t")), index: Box::new(num(0.0)) }, vec![neg(num(1.0))]),
+            Statement::PrintColoredDyn(num(2.0), vec![neg(num(1.0))]),
+        ]);
+    }
+
+    #[test]
+    fn lsd_looks_past_comments() {
+        let uncommented = |source: &str| -> Vec<Statement> {
+            let program = Parser::new(Lexer::new(source).with_comments()).parse()
+                .unwrap_or_else(|err| panic!("{:?} should parse: {}", source, err));
+            program.into_iter().map(|statement| match statement {
+                Statement::Commented { statement, .. } => *statement,
+                statement => statement,
+            }).collect()
+        };
+        for source in [
+            "lsd rgb /* c */ (1, 2, 3) \"a\";",
+            "lsd f /* c */ (1) \"a\";",
+            "lsd t /* c */ [0] \"a\";",
+            "lsd rouge /* c */ (1);",
+            "lsd rouge /* c */ \"a\";",
+            "lsd f /* a */ /* b */ (1) \"a\";",
+            "lsd f # c\n(1) \"a\";",
+        ] {
+            let plain = source.replace("/* c */", "").replace("/* a */ /* b */", "").replace("# c\n", "");
+            assert_eq!(uncommented(source), ast(&plain), "{:?}", source);
+        }
+    }
+
+    #[test]
+    fn colors_by_name() {
+        for (index, name) in Colored::NAMES.iter().enumerate() {
+            let color = Colored::from_name(name).unwrap_or_else(|| panic!("'{}' should be a color", name));
+            assert_eq!(format!("{:?}", color), format!("{:?}", Colored::from_index(index)));
+        }
+        assert!(matches!(Colored::from_name("rouge"), Some(Colored::Red)));
+        assert!(matches!(Colored::from_name("rose"), Some(Colored::Pink)));
+        assert!(matches!(Colored::from_name("multi"), Some(Colored::MultiColor)));
+        assert!(Colored::from_name("Red").is_none());
+        assert!(Colored::from_name("magenta").is_none());
+    }
+
+    #[test]
+    fn lsd_accepts_every_color_name() {
+        let program = ast("lsd purple 3; lsd brown 1; lsd cyan 2; lsd vert 4;");
+        assert!(matches!(program.as_slice(), [Statement::PrintColored(Colored::Purple, _, _), Statement::PrintColored(Colored::Brown, _, _),
+            Statement::PrintColored(Colored::Cyan, _, _), Statement::PrintColored(Colored::Green, _, _)]));
+        assert_eq!(parse_error("lsd magenta 3;"), "[PARSER] Error : Unrecognised color type 'magenta' \
+            (expected one of red, blue, green, yellow, purple, cyan, orange, white, brown, pink, multicolor)");
+    }
+
+    #[test]
+    fn lsd_with_rgb_components() {
+        assert!(matches!(&ast("lsd rgb(255, x * 2, 0) 42;")[0], Statement::PrintColoredRgb(components, values)
+            if matches!(values.as_slice(), [Expression::Number(_)]) && matches!(components.as_ref(), [Expression::Number(_), Expression::Binary { .. }, Expression::Number(_)])));
+        assert_eq!(parse_error("lsd rgb(1, 2) 3;"), "[PARSER] Error : 'rgb' expects 3 components (red, green, blue), got 2");
+        // Without parentheses `rgb` is just an unknown color name.
+        assert!(parse_error("lsd rgb 3;").starts_with("[PARSER] Error : Unrecognised color type 'rgb'"));
+    }
+
+    #[test]
+    fn keyword_arguments_are_read_from_the_current_token() {
+        let mut parser = Parser::new(Lexer::new("vicer 5 3;"));
+        assert_eq!(parser.parse().unwrap_err().to_string(), "[PARSER] Error : Expected a variable name, found number 5");
+        // The 5 is still the current token, so the `;` after 3 has not been consumed.
+        assert!(matches!(parser.current, Some(Token::Number(n)) if n == 5.0));
+        assert!(matches!(parser.tokens.peek(), Some(Ok(Token::Number(n))) if *n == 3.0));
+
+        assert!(matches!(&ast("lsd red x + 1;")[0], Statement::PrintColored(Colored::Red, _, values)
+            if matches!(values.as_slice(), [Expression::Binary { op: BinaryExpressionType::Sum, .. }])));
+        assert!(matches!(&ast("lsd red (1+2);")[0], Statement::PrintColored(Colored::Red, _, values)
+            if matches!(values.as_slice(), [Expression::Binary { .. }])));
+        assert!(matches!(ast("vicer x 1;\nvicer y 3;").as_slice(), [Statement::Assignment(x, _), Statement::Assignment(y, _)] if x == "x" && y == "y"));
+        assert_eq!(parse_error("vicer x\nvicer y 3;"), "[PARSER] Error : 'vicer' is a reserved keyword");
+        assert_eq!(parse_error("vicer si 1;"), "[PARSER] Error : 'si' is a reserved keyword");
+    }
+
+    #[test]
+    fn print_several_values() {
+        assert!(matches!(&ast("zipette a, b;")[0], Statement::Print(values) if values.len() == 2));
+        assert!(matches!(&ast("zipette 1, (2 + 3), f(4, 5), [6, 7], (8);")[0], Statement::Print(values)
+            if matches!(values.as_slice(), [Expression::Number(_), Expression::Binary { .. }, Expression::Call { .. }, Expression::Array(_), Expression::Number(_)])));
+        assert!(matches!(&ast("lsd blue 1, 2;")[0], Statement::PrintColored(Colored::Blue, _, values) if values.len() == 2));
+        assert_eq!(parse_error("zipette 1, 2,;"), "[PARSER] Error : Expected a value after ',', found ';'");
+        assert_eq!(parse_error("lsd red 1,;"), "[PARSER] Error : Expected a value after ',', found ';'");
+    }
+
+    #[test]
+    fn write_and_empty_print() {
+        assert!(matches!(ast("ecrit 1, x; zipette;").as_slice(), [Statement::Write(values), Statement::Print(empty)]
+            if values.len() == 2 && empty.is_empty()));
+        assert_eq!(parse_error("ecrit;"), "[PARSER] Error : Expected an expression, found ';'");
+        assert_eq!(parse_error("zipette"), "[PARSER] Error : Unexpected end of file, expected an expression");
+    }
+
+    #[test]
+    fn input_statement() {
+        assert!(matches!(ast("demande x; demande y \"y ? \";").as_slice(),
+            [Statement::Input { name: x, prompt: None }, Statement::Input { name: y, prompt: Some(Expression::Str(_)) }] if x == "x" && y == "y"));
+        assert_eq!(parse_error("demande 3;"), "[PARSER] Error : Expected a variable name after 'demande', found number 3");
+    }
+
+    #[test]
+    fn constant_declaration() {
+        assert!(matches!(&ast("fixe PI 3.14159;")[0], Statement::Constant(name, Expression::Number(_)) if name == "PI"));
+        assert_eq!(parse_error("fixe 3 1;"), "[PARSER] Error : Expected a constant name after 'fixe', found number 3");
+        assert_eq!(parse_error("vicer fixe 1;"), "[PARSER] Error : 'fixe' is a reserved keyword");
+    }
+
+    #[test]
+    fn random_expression() {
+        assert_eq!(ast("zipette hasard, hasard();"), [Statement::Print(vec![Expression::Random(None), Expression::Random(None)])]);
+        assert_eq!(ast("zipette hasard(1, n) * 2;"), [Statement::Print(vec![binary(BinaryExpressionType::Product,
+            Expression::Random(Some(Box::new([num(1.0), var("n")]))), num(2.0))])]);
+        assert_eq!(parse_error("zipette hasard(1);"),
+            "[PARSER] Error : 'hasard' expects no arguments or a minimum and a maximum, got 1 argument(s)");
+        assert_eq!(parse_error("vicer hasard 1;"), "[PARSER] Error : 'hasard' is a reserved keyword");
+    }
+
+    #[test]
+    fn clock_expression() {
+        assert_eq!(ast("vicer debut horloge;"), [Statement::Assignment("debut".to_string(), Expression::Clock)]);
+        assert_eq!(parse_error("vicer horloge 1;"), "[PARSER] Error : 'horloge' is a reserved keyword");
+        assert_eq!(parse_error("zipette horloge();"), "[PARSER] Error : Unexpected end of statement (; required), found '('");
+    }
+
+    #[test]
+    fn import_statement() {
+        assert_eq!(ast("importe \"utils.zipette\";"), [Statement::Import("utils.zipette".to_string())]);
+        assert_eq!(parse_error("importe utils;"), "[PARSER] Error : Expected a file path after 'importe', found identifier 'utils'");
+        assert_eq!(parse_error("importe"), "[PARSER] Error : Unexpected end of file, expected a file path");
+    }
+
+    #[test]
+    fn forget_statement() {
+        assert_eq!(ast("oublie x;"), [Statement::Forget("x".to_string())]);
+        assert_eq!(parse_error("oublie x + 1;"), "[PARSER] Error : Unexpected end of statement (; required), found '+'");
+        assert_eq!(parse_error("oublie 3;"), "[PARSER] Error : Expected a variable name after 'oublie', found number 3");
+        assert_eq!(parse_error("vicer oublie 1;"), "[PARSER] Error : 'oublie' is a reserved keyword");
+    }
+
+    #[test]
+    fn expressions_print_as_source() {
+        let reprint = |source: &str| printed(&ast(&format!("zipette {};", source))[0]).to_string();
+        for source in [
+            "1 + 2 * 3", "(1 + 2) * 3", "1 - (2 - 3)", "1 - 2 - 3", "2 ** 3 ** 2", "(2 ** 3) ** 2", "-2 ** 2", "(-2) ** 2",
+            "2 ** -1", "-2 ** -2", "(-2) ** -2", "-(a + b)", "-(-x)", "~(a | b) & c", "a == (b < c)", "(a == b) < c", "a || b && c", "(a || b) && c",
+            "a ? b : c ? d : e", "(a ? b : c) ? d : e", "t[i + 1][0]", "f(x, [1, \"deux\"], g())", "1 << 2 + 3", "a xor b | c",
+        ] {
+            assert_eq!(reprint(source), source);
+        }
+        assert_eq!(reprint("((x))  *(y)"), "x * y");
+    }
+
+    #[test]
+    fn assert_statement() {
+        assert!(matches!(&ast("verifie x == 5;")[0], Statement::Assert(Expression::Binary { op: BinaryExpressionType::Eq, .. })));
+        assert_eq!(parse_error("verifie;"), "[PARSER] Error : Expected an expression, found ';'");
+    }
+
+    #[test]
+    fn exit_statement() {
+        assert!(matches!(ast("quitte; quitte 2 + 1;").as_slice(), [Statement::Exit(None), Statement::Exit(Some(Expression::Binary { .. }))]));
+    }
+
+    #[test]
+    fn sleep_statement() {
+        assert!(matches!(&ast("attend delai * 2;")[0], Statement::Sleep(Expression::Binary { op: BinaryExpressionType::Product, .. })));
+        assert_eq!(parse_error("attend;"), "[PARSER] Error : Expected an expression, found ';'");
+    }
+
+    #[test]
+    fn parse_errors_report_line_and_column() {
+        assert_eq!(located_error("zipettes 1;"), "[PARSER] Error at line 1, column 1: Unexpected identifier 'zipettes'");
+        assert_eq!(located_error("zipette 1;\nzipette 2;\n  zipette 3 4;"),
+            "[PARSER] Error at line 3, column 13: Unexpected end of statement (; required), found number 4");
+        assert_eq!(located_error("zipette 1;\n\nlsd bleux 2;\nzipette 3;"),
+            "[PARSER] Error at line 3, column 5: Unrecognised color type 'bleux' (expected one of red, blue, green, yellow, purple, cyan, orange, white, brown, pink, multicolor)");
+        assert_eq!(located_error("zipette 1;\nzipette \"${1 +}\";"),
+            "[PARSER] Error at line 2, column 15: Unexpected end of file, expected an expression");
+        assert_eq!(located_error("zipette 1;\nzipette 2 *"), "[PARSER] Error at line 2, column 12: Unexpected end of file, expected an expression");
+        assert_eq!(located_error("zipette 1;\n  @"), "[PARSER] Error at line 2, column 3: LexerError: Unexpected character '@'");
+    }
+
+    #[test]
+    fn parse_all_reports_every_error() {
+        let source = "zipette 1;\nzipette (1 + ;\nvicer x 2;\nlsd bleux x;\nzipette x;\nzipettes 3;\nzipette 4;";
+        let errors = parse_all(source).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|err| err.loc.as_ref().unwrap().line).collect();
+        assert_eq!(lines, [2, 4, 6]);
+        assert_eq!(errors[2].to_string(), "[PARSER] Error at line 6, column 1: Unexpected identifier 'zipettes'");
+    }
+
+    #[test]
+    fn parse_all_resumes_after_the_failed_statement() {
+        let lines = |source| parse_all(source).unwrap_err().iter().map(|err| err.loc.as_ref().unwrap().line).collect::<Vec<_>>();
+        // The `;` an error stops at still ends the statement.
+        assert_eq!(lines("fixe;\nzipette 1;\ndemande;\nzipette 2;\nzipette (3 + ;\nzipette 4;\nzipette );"), [1, 3, 5, 7]);
+        // An error in a block skips the whole block, not just its statement.
+        assert_eq!(lines("si 1 {\n  zipette 1 2;\n  zipette 3;\n};\nzipettes 4;\nboucle 2 { { zipette ]; }; }\nzipettes 5;"), [2, 5, 6, 7]);
+        assert_eq!(lines("fonction f(a b) {\n  renvoie 1;\n}\nzipette f(1;"), [1, 4]);
+    }
+
+    #[test]
+    fn parse_all_keeps_lexer_errors_and_valid_programs() {
+        let errors = parse_all("@ zipette 1;\nzipette 2 @;\nvicer x 1 @ @;").unwrap_err();
+        assert_eq!(errors.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+            "[PARSER] Error at line 1, column 1: LexerError: Unexpected character '@'",
+            "[PARSER] Error at line 2, column 11: LexerError: Unexpected character '@'",
+            "[PARSER] Error at line 3, column 11: LexerError: Unexpected character '@'",
+            "[PARSER] Error at line 3, column 13: LexerError: Unexpected character '@'",
+        ]);
+        let program = parse_all("zipette 1;\nzipette 2;").unwrap();
+        assert_eq!(program.len(), 2);
+    }
+
+    #[test]
+    fn statements_print_as_source() {
+        let program = ast("vicer x 1+2; x *= (3 - 1) ** 2; lsd rouge x, -(-x); si x { zipette; } sinon si ~x { casse_pas(); };");
+        let printed: Vec<String> = program.iter().map(Statement::to_string).collect();
+        assert_eq!(printed, [
+            "vicer x 1 + 2;",
+            "x *= (3 - 1) ** 2;",
+            "lsd rouge x, -(-x);",
+            "si x {\n    zipette;\n} sinon si ~x {\n    casse_pas();\n};",
+        ]);
+    }
+
+    #[test]
+    fn comments_print_around_their_statement() {
+        let source = "# Aire d'un carré\nvicer c 3;\nsi c > 2 {\n  # grand\n  zipette c * c; # le carré\n};\nzipette c # fin\n";
+        let program = Parser::new(Lexer::new(source).with_comments()).parse().unwrap();
+        assert!(matches!(&program[0], Statement::Commented { comments, statement, trailing: None }
+            if comments == &["# Aire d'un carré"] && matches!(**statement, Statement::Assignment(..))));
+        let printed: String = program.iter().map(|statement| format!("{}\n", statement)).collect();
+        assert_eq!(printed, "# Aire d'un carré\nvicer c 3;\nsi c > 2 {\n    # grand\n    zipette c * c; # le carré\n};\nzipette c; # fin\n");
+        assert_eq!(Parser::new(Lexer::new(&printed).with_comments()).parse().unwrap(), program);
+        // Without comments from the lexer, the tree has none.
+        assert!(!ast(source).iter().any(|statement| matches!(statement, Statement::Commented { .. })));
+    }
+
+    #[test]
+    fn comments_before_a_closing_brace_stay_in_the_block() {
+        let source = "si 1 {\n  zipette 1;\n  # fin du si\n};\nzipette 2;\nfonction f() {\n    # rien\n};\n";
+        let program = Parser::new(Lexer::new(source).with_comments()).parse().unwrap();
+        assert!(matches!(&program[0], Statement::If { then_block, .. }
+            if matches!(then_block.as_slice(), [Statement::Print(_), Statement::Comments(comments)] if comments == &["# fin du si"])));
+        assert!(matches!(&program[1], Statement::Print(_)));
+        let printed: String = program.iter().map(|statement| format!("{}\n", statement)).collect();
+        assert_eq!(printed, "si 1 {\n    zipette 1;\n    # fin du si\n};\nzipette 2;\nfonction f() {\n    # rien\n};\n");
+        assert_eq!(Parser::new(Lexer::new(&printed).with_comments()).parse().unwrap(), program);
+    }
+
+    #[test]
+    fn format_source_is_canonical_and_idempotent() {
+        let messy = include_str!("../tests/fixtures/messy.zipette");
+        let formatted = format_source(messy).unwrap();
+        assert_eq!(formatted, concat!(
+            "#!/usr/bin/env zipette\n",
+            "# Un fichier écrit sans soin, pour le formateur.\n",
+            "vicer x 3.5;\n",
+            "vicer y (x + 1) * 2;\n",
+            "fixe N 1000; # mille\n",
+            "zipette x, y, N;\n",
+            "/* calcule\n   une aire */\n",
+            "fonction aire(l, h = l) {\n    renvoie l * h;\n};\n",
+            "si x > y && ~(x == 0 || y == 0) {\n    zipette \"grand ${x + 1}\";\n} sinon si x < 0 {\n    zipette;\n} sinon {\n",
+            "    # rien à dire\n    ecrit \"petit\";\n};\n",
+            "boucle 3 {\n    si indice % 2 == 0 {\n        continue;\n    };\n    x += -(-indice);\n};\n",
+            "selon x {\n    1: {\n        zipette \"un\";\n    }\n    3.5: {\n        zipette \"trois et demi\";\n    }\n    autre: {\n    }\n};\n",
+            "fais {\n    x -= 1;\n} tantque x > 0;\n",
+            "lsd rouge aire(2), 2 ** 3 ** 2, (2 ** 3) ** 2, -2 ** 2;\n",
+            "vicer t [1, [2, 3], \"a\"];\n",
+            "zipette t[1][0];\n",
+            "vicer i 0;\n",
+            "i++;\n",
+            "--i;\n",
+            "i -= 2;\n",
+            "si i {\n    zipette i; /* i */\n    zipette -i; # fin du si\n    # rien après\n};\n",
+            "zipette x ? y : N;\n",
+            "zipette hasard(1, 2) + horloge * 0.5; # dernier\n",
+            "# plus rien\n",
+        ));
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+        assert_eq!(format_source("zipette (;").unwrap_err().to_string(), "[PARSER] Error : Expected an expression, found ';'");
+    }
+
+    #[test]
+    fn format_source_keeps_comments_and_shorthands() {
+        let comments = |source: &str| -> Vec<String> {
+            Lexer::new(source).with_comments().filter_map(|token| match token {
+                Ok(Token::Comment { text, .. }) => Some(text),
+                _ => None,
+            }).collect()
+        };
+        for source in [
+            include_str!("../tests/fixtures/messy.zipette"),
+            include_str!("../tests/fixtures/arguments.zipette"),
+            "x += 1; x++; ++x; i -= 2; i--; --i; x *= -(-y); x /= 2;",
+            "lsd rouge 1; lsd red 2; lsd multi 3; lsd rose x;",
+            "vicer x 1 /* a */ + /* b */ 2; # c\n# d\n",
+            "zipette 1 +\n# dedans\n2; # après\nzipette 3;",
+            "si 1 { zipette 1; # fin du si\n}",
+            "{ # rien\n};\n# a\n\n# b",
+            "fonction f() {\n  boucle 2 {\n    # dedans\n  };\n  # après la boucle\n};",
+        ] {
+            let formatted = format_source(source).unwrap_or_else(|err| panic!("{:?} should format: {}", source, err));
+            assert_eq!(format_source(&formatted).unwrap(), formatted, "{:?}", source);
+            assert_eq!(ast(&formatted), ast(source), "{:?}", source);
+            assert_eq!(comments(&formatted), comments(source), "{:?}", source);
+        }
+        assert_eq!(format_source("x += 1; x++; ++x; i -= 2; x += -(-indice);").unwrap(), "x += 1;\nx++;\n++x;\ni -= 2;\nx += -(-indice);\n");
+        assert_eq!(format_source("lsd rouge 1; lsd red 2;").unwrap(), "lsd rouge 1;\nlsd red 2;\n");
+        assert_eq!(format_source("si 1 { zipette 1; # fin du si\n}").unwrap(), "si 1 {\n    zipette 1; # fin du si\n};\n");
+        assert_eq!(format_source("si 1 {\n  zipette 1;\n  # fin du si\n}\n# fin").unwrap(), "si 1 {\n    zipette 1;\n    # fin du si\n};\n# fin\n");
+    }
+
+    #[test]
+    fn printed_program_parses_back_to_the_same_ast() {
+        let source = r#"
+            fixe n 10;
+            vicer total: nombre 0;
+            vicer t [1, [2, 3], "a"];
+            fonction f(a, b = a * 2) {
+                si a > b && ~(a == 0 || b == 0) { renvoie a - (b - 1); } sinon { renvoie a ? b : -a ** 2; };
+            };
+            fais { n -= 1; } tantque n > 5;
+            boucle n { si indice % 2 == 0 { continue; }; total += f(indice, 2) << 1 | 3 xor 4 & ~5; };
+            tantque total >= 0 { total--; si total < 3 { casse; }; selon total { 1: { } n ? 2 : 3: { casse; } autre: { zipette; } }; };
+            { zipette (1 + 2) * 3, t[1][0], 2 ** 3 ** 2, (2 ** 3) ** 2, (1 < 2) == (2 > 1); };
+            zipette "total ${total + 1}!";
+            ecrit t, "x";
+            lsd multi total;
+            lsd (n % 11) ([1]), 2;
+            lsd rgb(255, n * 2, 0) "rgb";
+            demande x "nombre ?";
+            demande y;
+            oublie y;
+            importe "constantes.zipette";
+            verifie total <= n;
+            attend 10;
+            zipette hasard * 2, -hasard(1, n), horloge - 1;
+            f(1, 2);
+            quitte n - 10;
+            quitte;
+        "#;
+        let program = ast(source);
+        let printed: String = program.iter().map(|statement| format!("{}\n", statement)).collect();
+        let reparsed = parse(&printed)
+            .unwrap_or_else(|err| panic!("{}\n{}", err, printed));
+        assert_eq!(reparsed, program, "{}", printed);
+    }
+
+    fn num(n: f64) -> Expression {
+        Expression::Number(n)
+    }
+
+    fn var(name: &str) -> Expression {
+        Expression::Identifier(name.to_string())
+    }
+
+    fn binary(op: BinaryExpressionType, left: Expression, right: Expression) -> Expression {
+        Expression::Binary { op, left: Box::new(left), right: Box::new(right) }
+    }
+
+    #[test]
+    fn ast_of_assignments_and_prints() {
+        assert_eq!(ast("vicer x 1 + 2 * 3; x -= 1; zipette x, -x;"), [
+            Statement::Assignment("x".to_string(), binary(BinaryExpressionType::Sum, num(1.0),
+                binary(BinaryExpressionType::Product, num(2.0), num(3.0)))),
+            Statement::CompoundAssignment("x".to_string(), BinaryExpressionType::Minus, num(1.0)),
+            Statement::Print(vec![var("x"), Expression::Unary { op: UnaryOp::Neg, expr: Box::new(var("x")) }]),
+        ]);
+    }
+
+    #[test]
+    fn ast_of_colored_prints() {
+        assert_eq!(ast("lsd rouge 1; lsd (c) \"a\"; lsd rgb(1, 2, 3) x;"), [
+            Statement::PrintColored(Colored::Red, "rouge".to_string(), vec![num(1.0)]),
+            Statement::PrintColoredDyn(var("c"), vec![Expression::Str("a".to_string())]),
+            Statement::PrintColoredRgb(Box::new([num(1.0), num(2.0), num(3.0)]), vec![var("x")]),
+        ]);
+    }
+
+    #[test]
+    fn ast_of_conditions() {
+        assert_eq!(ast("si x < 2 && y { zipette 1; } sinon si z { } sinon { zipette; };"), [
+            Statement::If {
+                cond: Expression::Logical {
+                    op: LogicalOp::And,
+                    left: Box::new(binary(BinaryExpressionType::Lt, var("x"), num(2.0))),
+                    right: Box::new(var("y")),
+                },
+                then_block: vec![Statement::Print(vec![num(1.0)])],
+                else_block: Some(vec![Statement::If {
+                    cond: var("z"),
+                    then_block: vec![],
+                    else_block: Some(vec![Statement::Print(vec![])]),
+                }]),
+            },
+        ]);
+    }
+
+    #[test]
+    fn ast_of_loops() {
+        assert_eq!(ast("tantque i { i--; casse; }; boucle 3 { continue; };"), [
+            Statement::While {
+                cond: var("i"),
+                body: vec![
+                    Statement::Step { name: "i".to_string(), op: BinaryExpressionType::Minus, prefix: false },
+                    Statement::Break,
+                ],
+            },
+            Statement::Repeat { count: num(3.0), body: vec![Statement::Continue] },
+        ]);
+    }
+
+    #[test]
+    fn ast_of_functions() {
+        let function = Function {
+            name: "f".to_string(),
+            params: vec![Param { name: "a".to_string(), default: None }, Param { name: "b".to_string(), default: None }],
+            body: vec![Statement::Return(Expression::Conditional {
+                cond: Box::new(var("a")),
+                then: Box::new(Expression::Index { target: Box::new(var("b")), index: Box::new(num(0.0)) }),
+                otherwise: Box::new(Expression::Array(vec![])),
+            })],
+        };
+        let call = Expression::Call { name: "f".to_string(), args: vec![num(1.0), var("t")] };
+        assert_eq!(ast("fonction f(a, b) { renvoie a ? b[0] : []; }; f(1, t);"), [
+            Statement::FunctionDef(Rc::new(function)),
+            Statement::Expression(call),
+        ]);
+    }
+
+    #[test]
+    fn statements_can_be_cloned() {
+        let program = ast("boucle 2 { zipette \"tour ${indice}\"; };");
+        assert_eq!(program.clone(), program);
+    }
+
+    #[test]
+    fn last_semicolon_is_optional() {
+        assert_eq!(ast("zipette 1"), [Statement::Print(vec![num(1.0)])]);
+        assert_eq!(ast("vicer x 1;\nzipette x  \n# fin\n/* vraiment */\n"), ast("vicer x 1; zipette x;"));
+        assert_eq!(ast("si x { zipette 1; }"), ast("si x { zipette 1; };"));
+        assert_eq!(parse_error("zipette 1 zipette 2;"), "[PARSER] Error : Unexpected end of statement (; required), found keyword 'zipette'");
+        assert_eq!(parse_error("{ zipette 1 }"), "[PARSER] Error : Unexpected end of statement (; required), found '}'");
+        assert_eq!(parse_error("{ zipette 1;"), "[PARSER] Error : Unterminated block: expected '}' after 1 statement(s)");
+    }
+
+    #[test]
+    fn empty_statements_are_skipped() {
+        assert_eq!(ast(";;; zipette 1;;"), [Statement::Print(vec![num(1.0)])]);
+        assert_eq!(ast("si x { ; zipette 1;; };"), ast("si x { zipette 1; };"));
+        assert_eq!(parse_all(";\n;").unwrap(), []);
+    }
+
+    #[test]
+    fn exponent_binds_tighter_than_unary_minus() {
+        let neg = |expr| Expression::Unary { op: UnaryOp::Neg, expr: Box::new(expr) };
+        let pow = |left, right| binary(BinaryExpressionType::Exponent, left, right);
+        let expression = |source: &str| printed(&ast(&format!("zipette {};", source))[0]).clone();
+        assert_eq!(expression("-2 ** 2"), neg(pow(num(2.0), num(2.0))));
+        assert_eq!(expression("(-2) ** 2"), pow(neg(num(2.0)), num(2.0)));
+        assert_eq!(expression("2 ** -1"), pow(num(2.0), neg(num(1.0))));
+        assert_eq!(expression("-2 ** -2"), neg(pow(num(2.0), neg(num(2.0)))));
+    }
+
+    #[test]
+    fn selon_syntax_errors() {
+        assert_eq!(parse_error("selon x { autre: { } 1: { } };"), "[PARSER] Error : 'autre' must be the last arm of 'selon'");
+        assert_eq!(parse_error("selon x { 1 { } };"), "[PARSER] Error : Expected ':' after the value of an arm, found '{'");
+        assert_eq!(parse_error("selon x { 1: zipette 1; };"), "[PARSER] Error : Expected '{' after the ':' of a 'selon' arm, found keyword 'zipette'");
+        assert_eq!(parse_error("selon x 1: { };"), "[PARSER] Error : Expected '{' after the value of 'selon', found number 1");
+        assert_eq!(parse_error("selon x { 1: { }"), "[PARSER] Error : Unterminated 'selon': expected '}' after its arms");
+        assert_eq!(parse_error("vicer autre 1;"), "[PARSER] Error : 'autre' is a reserved keyword");
+    }
+
+    #[test]
+    fn fais_syntax() {
+        assert_eq!(ast("fais { casse; } tantque x;"), [Statement::DoWhile { body: vec![Statement::Break], cond: var("x") }]);
+        assert_eq!(parse_error("fais { } x;"), "[PARSER] Error : Expected 'tantque' after the block of 'fais', found identifier 'x'");
+        assert_eq!(parse_error("fais zipette 1; tantque x;"), "[PARSER] Error : Expected '{' after 'fais', found keyword 'zipette'");
+        assert_eq!(parse_error("fais { } tantque x { };"), "[PARSER] Error : Unexpected end of statement (; required), found '{'");
+    }
+
+    #[test]
+    fn interpolation_errors_point_into_the_string() {
+        assert_eq!(located_error("vicer x 1;\n  zipette \"a ${x} b ${(x + )}\";"),
+            "[PARSER] Error at line 2, column 28: Expected an expression, found ')'");
+        assert_eq!(located_error("zipette \"ligne\nsuivante ${x y}\";"), "[PARSER] Error at line 2, column 14: Unexpected identifier 'y' in '${x y}'");
+        assert_eq!(located_error("zipette \"${@}\";"), "[PARSER] Error at line 1, column 12: LexerError: Unexpected character '@'");
+    }
+}
@@ -0,0 +1,340 @@
+//! Lisp-style dump of the syntax tree, one statement per line, meant to be
+//! read and diffed while debugging the parser.
+//!
+//! Statements are written `(print ...)`, `(write ...)`, `(input name prompt?)`,
+//! `(print-colored color value...)` where the color is a name such as `red`,
+//! `(color expr)` or `(rgb r g b)`, `(assign name value)`, `(assign-op name +=
+//! value)`, `(step name ++)` or `(step ++ name)` as written, `(assign-typed
+//! name type value)`, `(const name value)`, `(forget name)`, `(import
+//! "file")`, `(assert cond)`, `(exit code?)`, `(sleep ms)`, `(block
+//! statement...)`, `(if cond (block...) (block...)?)`, `(while cond
+//! (block...))`, `(do-while (block...) cond)`, `(repeat count (block...))`,
+//! `(match subject (arm pattern (block...))... (default (block...))?)`,
+//! `(function name (param...) (block...))` where a parameter with a default is
+//! `(param name default)`, `(return value)`, `(break)`, `(continue)`, `(expr
+//! expression)`, `(commented ("comment"...) statement "trailing"?)` and
+//! `(comments "comment"...)`.
+//!
+//! Expressions are `(num n)`, `(str "text")`, `(var name)`, `(array e...)`,
+//! `(interpolate part...)` whose parts are `(str "text")` or expressions,
+//! `(index target index)`, `(call name arg...)`, `(random)` or `(random min
+//! max)`, `(clock)`, `(cond cond then otherwise)`, `(and a b)`, `(or a b)`,
+//! `(neg e)`, `(bitnot e)`, and binary operations named by their source symbol:
+//! `+ - * / % ** << >> & | xor == != < > <= >=`.
+
+use crate::parser::{Colored, Expression, LogicalOp, Statement, StringPart, UnaryOp};
+
+/// Dumps `program`, one top-level statement per line, with the names listed
+/// above. They are part of the format and only ever get added to.
+pub fn to_sexpr(program: &[Statement]) -> String {
+    program.iter().map(|statement| statement.to_sexpr() + "\n").collect()
+}
+
+impl Statement {
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        write_statement(&mut out, self);
+        out
+    }
+}
+
+impl Expression {
+    pub fn to_sexpr(&self) -> String {
+        let mut out = String::new();
+        write_expression(&mut out, self);
+        out
+    }
+}
+
+fn write_statement(out: &mut String, statement: &Statement) {
+    match statement {
+        Statement::Expression(expr) => list(out, "expr", |out| write_all(out, std::slice::from_ref(expr))),
+        Statement::Print(values) => list(out, "print", |out| write_all(out, values)),
+        Statement::Write(values) => list(out, "write", |out| write_all(out, values)),
+        Statement::Input { name, prompt } => list(out, "input", |out| {
+            out.push(' ');
+            out.push_str(name);
+            if let Some(prompt) = prompt {
+                out.push(' ');
+                write_expression(out, prompt);
+            }
+        }),
+        Statement::PrintColored(color, _, values) => list(out, "print-colored", |out| {
+            out.push(' ');
+            match color {
+                Colored::Custom(red, green, blue) => out.push_str(&format!("(rgb (num {}) (num {}) (num {}))", red, green, blue)),
+                color => out.push_str(&color.to_string()),
+            }
+            write_all(out, values);
+        }),
+        Statement::PrintColoredDyn(color, values) => list(out, "print-colored", |out| {
+            out.push(' ');
+            list(out, "color", |out| write_all(out, std::slice::from_ref(color)));
+            write_all(out, values);
+        }),
+        Statement::PrintColoredRgb(components, values) => list(out, "print-colored", |out| {
+            out.push(' ');
+            list(out, "rgb", |out| write_all(out, &**components));
+            write_all(out, values);
+        }),
+        Statement::Assignment(name, value) => write_binding(out, "assign", name, value),
+        Statement::CompoundAssignment(name, op, value) => list(out, "assign-op", |out| {
+            out.push_str(&format!(" {} {}= ", name, op));
+            write_expression(out, value);
+        }),
+        Statement::Step { name, op, prefix: true } => out.push_str(&format!("(step {}{} {})", op, op, name)),
+        Statement::Step { name, op, prefix: false } => out.push_str(&format!("(step {} {}{})", name, op, op)),
+        Statement::Constant(name, value) => write_binding(out, "const", name, value),
+        Statement::Forget(name) => out.push_str(&format!("(forget {})", name)),
+        Statement::Import(file) => out.push_str(&format!("(import {:?})", file)),
+        Statement::TypedAssignment(name, value_type, value) => list(out, "assign-typed", |out| {
+            out.push_str(&format!(" {} {} ", name, value_type));
+            write_expression(out, value);
+        }),
+        Statement::Assert(cond) => list(out, "assert", |out| write_all(out, std::slice::from_ref(cond))),
+        Statement::Exit(code) => list(out, "exit", |out| write_all(out, code.as_slice())),
+        Statement::Sleep(duration) => list(out, "sleep", |out| write_all(out, std::slice::from_ref(duration))),
+        Statement::Block(statements) => write_block(out, statements),
+        Statement::If { cond, then_block, else_block } => list(out, "if", |out| {
+            write_all(out, std::slice::from_ref(cond));
+            out.push(' ');
+            write_block(out, then_block);
+            if let Some(else_block) = else_block {
+                out.push(' ');
+                write_block(out, else_block);
+            }
+        }),
+        Statement::While { cond, body } => write_loop(out, "while", cond, body),
+        Statement::DoWhile { body, cond } => list(out, "do-while", |out| {
+            out.push(' ');
+            write_block(out, body);
+            write_all(out, std::slice::from_ref(cond));
+        }),
+        Statement::Repeat { count, body } => write_loop(out, "repeat", count, body),
+        Statement::Match { subject, arms, default } => list(out, "match", |out| {
+            write_all(out, std::slice::from_ref(subject));
+            for (pattern, block) in arms {
+                out.push(' ');
+                write_loop(out, "arm", pattern, block);
+            }
+            if let Some(block) = default {
+                out.push_str(" (default ");
+                write_block(out, block);
+                out.push(')');
+            }
+        }),
+        Statement::FunctionDef(function) => list(out, "function", |out| {
+            out.push_str(&format!(" {} (", function.name));
+            for (i, param) in function.params.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                match &param.default {
+                    Some(default) => write_binding(out, "param", &param.name, default),
+                    None => out.push_str(&param.name),
+                }
+            }
+            out.push_str(") ");
+            write_block(out, &function.body);
+        }),
+        Statement::Return(value) => list(out, "return", |out| write_all(out, std::slice::from_ref(value))),
+        Statement::Break => out.push_str("(break)"),
+        Statement::Commented { comments, statement, trailing } => list(out, "commented", |out| {
+            let comments: Vec<String> = comments.iter().map(|comment| format!("{:?}", comment)).collect();
+            out.push_str(&format!(" ({}) ", comments.join(" ")));
+            write_statement(out, statement);
+            if let Some(comment) = trailing {
+                out.push_str(&format!(" {:?}", comment));
+            }
+        }),
+        Statement::Comments(comments) => list(out, "comments", |out| {
+            comments.iter().for_each(|comment| out.push_str(&format!(" {:?}", comment)));
+        }),
+        Statement::Continue => out.push_str("(continue)"),
+    }
+}
+
+fn write_binding(out: &mut String, head: &str, name: &str, value: &Expression) {
+    list(out, head, |out| {
+        out.push(' ');
+        out.push_str(name);
+        out.push(' ');
+        write_expression(out, value);
+    });
+}
+
+fn write_loop(out: &mut String, head: &str, cond: &Expression, body: &[Statement]) {
+    list(out, head, |out| {
+        write_all(out, std::slice::from_ref(cond));
+        out.push(' ');
+        write_block(out, body);
+    });
+}
+
+fn write_block(out: &mut String, statements: &[Statement]) {
+    list(out, "block", |out| {
+        for statement in statements {
+            out.push(' ');
+            write_statement(out, statement);
+        }
+    });
+}
+
+fn write_expression(out: &mut String, expr: &Expression) {
+    match expr {
+        Expression::Number(n) => out.push_str(&format!("(num {})", n)),
+        Expression::Str(text) => out.push_str(&format!("(str {:?})", text)),
+        Expression::Identifier(name) => out.push_str(&format!("(var {})", name)),
+        Expression::Array(elements) => list(out, "array", |out| write_all(out, elements)),
+        Expression::Index { target, index } => list(out, "index", |out| {
+            out.push(' ');
+            write_expression(out, target);
+            out.push(' ');
+            write_expression(out, index);
+        }),
+        Expression::Interpolation(parts) => list(out, "interpolate", |out| {
+            for part in parts {
+                out.push(' ');
+                match part {
+                    StringPart::Text(text) => out.push_str(&format!("(str {:?})", text)),
+                    StringPart::Expression(expr) => write_expression(out, expr),
+                }
+            }
+        }),
+        Expression::Clock => out.push_str("(clock)"),
+        Expression::Random(range) => list(out, "random", |out| {
+            if let Some(range) = range {
+                write_all(out, &**range);
+            }
+        }),
+        Expression::Call { name, args } => list(out, "call", |out| {
+            out.push(' ');
+            out.push_str(name);
+            write_all(out, args);
+        }),
+        Expression::Conditional { cond, then, otherwise } => list(out, "cond", |out| {
+            for expr in [cond, then, otherwise] {
+                out.push(' ');
+                write_expression(out, expr);
+            }
+        }),
+        Expression::Logical { op, left, right } => {
+            let head = match op { LogicalOp::And => "and", LogicalOp::Or => "or" };
+            write_operation(out, head, left, right);
+        }
+        Expression::Unary { op, expr } => {
+            let head = match op { UnaryOp::Neg => "neg", UnaryOp::BitNot => "bitnot" };
+            list(out, head, |out| write_all(out, std::slice::from_ref(&**expr)));
+        }
+        Expression::Binary { op, left, right } => write_operation(out, &op.to_string(), left, right),
+    }
+}
+
+fn write_operation(out: &mut String, head: &str, left: &Expression, right: &Expression) {
+    list(out, head, |out| {
+        out.push(' ');
+        write_expression(out, left);
+        out.push(' ');
+        write_expression(out, right);
+    });
+}
+
+/// Writes each expression preceded by a space.
+fn write_all(out: &mut String, exprs: &[Expression]) {
+    for expr in exprs {
+        out.push(' ');
+        write_expression(out, expr);
+    }
+}
+
+/// Writes `(head ...)`, `write_rest` writing what follows the head.
+fn list(out: &mut String, head: &str, write_rest: impl FnOnce(&mut String)) {
+    out.push('(');
+    out.push_str(head);
+    write_rest(out);
+    out.push(')');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn sexpr(source: &str) -> String {
+        to_sexpr(&Parser::new(Lexer::new(source)).parse().unwrap())
+    }
+
+    #[test]
+    fn expression_dump() {
+        let program = Parser::new(Lexer::new("zipette 1 + 2 * x;")).parse().unwrap();
+        assert_eq!(program[0].to_sexpr(), "(print (+ (num 1) (* (num 2) (var x))))");
+    }
+
+    #[test]
+    fn program_snapshot() {
+        let source = "
+            vicer x 1 + 2 - 3 * 4 / 5 % 6 ** 7;
+            vicer y x << 1 >> 2 & 3 | 4 xor 5;
+            vicer z: nombre x == 1 && x != 2 || x < 3 ? x > 4 : x <= 5 && x >= 6;
+            x += -~y * hasard + hasard(1, 2);
+            x++;
+            --x;
+            fixe t [1, \"a\"];
+            lsd red t[0], x;
+            lsd (x % 11) y;
+            lsd rgb(255, 0, x) \"rgb\";
+            zipette \"x = ${x}\", horloge;
+            ecrit 1;
+            si x { f(t, 1); } sinon { zipette; };
+            tantque x { casse; };
+            fais { casse; } tantque x;
+            boucle 3 { continue; };
+            selon x { 1: { zipette 1; } 2: { } autre: { zipette; } };
+            fonction f(a, b) { renvoie a; };
+            fonction g(a, b = a + 1) { };
+            demande n \"?\";
+            verifie n;
+            oublie n;
+            importe \"a.zipette\";
+            attend 1;
+            quitte 2;
+        ";
+        assert_eq!(sexpr(source), concat!(
+            "(assign x (- (+ (num 1) (num 2)) (% (/ (* (num 3) (num 4)) (num 5)) (** (num 6) (num 7)))))\n",
+            "(assign y (| (& (>> (<< (var x) (num 1)) (num 2)) (num 3)) (xor (num 4) (num 5))))\n",
+            "(assign-typed z nombre (cond (or (and (== (var x) (num 1)) (!= (var x) (num 2))) (< (var x) (num 3))) ",
+            "(> (var x) (num 4)) (and (<= (var x) (num 5)) (>= (var x) (num 6)))))\n",
+            "(assign-op x += (+ (* (neg (bitnot (var y))) (random)) (random (num 1) (num 2))))\n",
+            "(step x ++)\n",
+            "(step -- x)\n",
+            "(const t (array (num 1) (str \"a\")))\n",
+            "(print-colored red (index (var t) (num 0)) (var x))\n",
+            "(print-colored (color (% (var x) (num 11))) (var y))\n",
+            "(print-colored (rgb (num 255) (num 0) (var x)) (str \"rgb\"))\n",
+            "(print (interpolate (str \"x = \") (var x)) (clock))\n",
+            "(write (num 1))\n",
+            "(if (var x) (block (expr (call f (var t) (num 1)))) (block (print)))\n",
+            "(while (var x) (block (break)))\n",
+            "(do-while (block (break)) (var x))\n",
+            "(repeat (num 3) (block (continue)))\n",
+            "(match (var x) (arm (num 1) (block (print (num 1)))) (arm (num 2) (block)) (default (block (print))))\n",
+            "(function f (a b) (block (return (var a))))\n",
+            "(function g (a (param b (+ (var a) (num 1)))) (block))\n",
+            "(input n (str \"?\"))\n",
+            "(assert (var n))\n",
+            "(forget n)\n",
+            "(import \"a.zipette\")\n",
+            "(sleep (num 1))\n",
+            "(exit (num 2))\n",
+        ));
+    }
+
+    #[test]
+    fn commented_statements() {
+        let program = Parser::new(Lexer::new("# a\n# b\nzipette 1; # c\nzipette 2; /* d */").with_comments()).parse().unwrap();
+        assert_eq!(to_sexpr(&program), "(commented (\"# a\" \"# b\") (print (num 1)) \"# c\")\n(commented () (print (num 2)) \"/* d */\")\n");
+        let program = Parser::new(Lexer::new("{ zipette 1;\n# e\n# f\n}").with_comments()).parse().unwrap();
+        assert_eq!(to_sexpr(&program), "(block (print (num 1)) (comments \"# e\" \"# f\"))\n");
+    }
+}
@@ -0,0 +1,54 @@
+use std::process::Command;
+
+/// Runs the interpreter binary with `args`, returning whether it succeeded
+/// and what it printed after the banner.
+fn interpreter(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_my-interpreter"))
+        .args(args)
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("the interpreter should start");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let printed = stdout.split_once('\n').map(|(_, printed)| printed.to_string()).unwrap_or_default();
+    (output.status.success(), printed)
+}
+
+/// What a script that runs fine printed.
+fn run(args: &[&str]) -> String {
+    let (success, printed) = interpreter(args);
+    assert!(success, "{}", printed);
+    printed
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn script_arguments() {
+    let script = &fixture("arguments.zipette");
+    assert_eq!(run(&[script, "--", "3", "4"]), "7\n2 [3, 4]\n");
+    assert_eq!(run(&[script, "3", "4", "--", "--fold", "x"]), "7\n4 [3, 4, \"--fold\", \"x\"]\n");
+    assert_eq!(run(&["--fold", script, "1,5", "0x10"]), "17.5\n2 [1.5, 16]\n");
+}
+
+#[test]
+fn errors_are_reported_together_under_their_source_line() {
+    let (success, printed) = interpreter(&[&fixture("errors.zipette")]);
+    assert!(!success);
+    assert_eq!(printed, concat!(
+        "[PARSER] Error at line 2, column 9: LexerError: Unexpected character '@'\n",
+        "   2 | vicer x @;\n",
+        "     |         ^\n",
+        "[PARSER] Error at line 3, column 14: Expected an expression, found ';'\n",
+        "   3 | zipette (2 + ;\n",
+        "     |              ^\n",
+        // Tabs span four columns, in the position and in the line shown.
+        "[PARSER] Error at line 4, column 15: Unexpected end of statement (; required), found number 4\n",
+        "   4 |     zipette 3 4;\n",
+        "     |               ^\n",
+        "[PARSER] Error at line 5, column 19: Unexpected end of statement (; required), found number 6\n",
+        "   5 |       zipette   5 6;\n",
+        "     |                   ^\n",
+    ));
+}